@@ -0,0 +1,65 @@
+// Mathematical Alphanumeric Symbols, used where mathvariant is not available
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Variant {
+    Normal,
+    Italic,
+    Bold,
+    BoldItalic,
+}
+
+impl Variant {
+    pub(crate) fn new(bold: bool, italic: bool) -> Variant {
+        match (bold, italic) {
+            (false, false) => Variant::Normal,
+            (false, true) => Variant::Italic,
+            (true, false) => Variant::Bold,
+            (true, true) => Variant::BoldItalic,
+        }
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Variant::Normal => "normal",
+            Variant::Italic => "italic",
+            Variant::Bold => "bold",
+            Variant::BoldItalic => "bold-italic",
+        }
+    }
+}
+
+// First code point of capital Latin, small Latin, capital Greek and small Greek letters
+fn bases(variant: Variant) -> Option<[u32; 4]> {
+    match variant {
+        Variant::Normal => None,
+        Variant::Bold => Some([0x1D400, 0x1D41A, 0x1D6A8, 0x1D6C2]),
+        Variant::Italic => Some([0x1D434, 0x1D44E, 0x1D6E2, 0x1D6FC]),
+        Variant::BoldItalic => Some([0x1D468, 0x1D482, 0x1D71C, 0x1D736]),
+    }
+}
+
+pub(crate) fn styled(text: &str, variant: Variant) -> String {
+    let Some([capital, small, greek_capital, greek_small]) = bases(variant) else {
+        return text.to_string();
+    };
+
+    text.chars()
+        .map(|ch| {
+            let code = ch as u32;
+            let mapped = match ch {
+                // Italic small h predates the block and lives in Letterlike Symbols
+                'h' if variant == Variant::Italic => Some(0x210E),
+                'A'..='Z' => Some(capital + code - 'A' as u32),
+                'a'..='z' => Some(small + code - 'a' as u32),
+                'Α'..='Ω' => Some(greek_capital + code - 'Α' as u32),
+                'α'..='ω' => Some(greek_small + code - 'α' as u32),
+                // Only bold digits exist, italic digits stay as they are
+                '0'..='9' if matches!(variant, Variant::Bold | Variant::BoldItalic) => {
+                    Some(0x1D7CE + code - '0' as u32)
+                }
+                _ => None,
+            };
+            mapped.and_then(char::from_u32).unwrap_or(ch)
+        })
+        .collect()
+}
@@ -0,0 +1,139 @@
+use crate::{INVISIBLE_TIMES, OperatorKind};
+
+// Parsed StarMath, independent of the output format
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
+pub(crate) enum Node {
+    Number(String),
+    Identifier(String),
+    // Standard or registered function name, written upright
+    Function(String),
+    Operator {
+        symbol: String,
+        // Only set for operators coming from the registry
+        kind: Option<OperatorKind>,
+        precedence: u8,
+    },
+    Text(String),
+    Row(Vec<Node>),
+    Sub(Box<Node>, Box<Node>),
+    Sup(Box<Node>, Box<Node>),
+    Frac(Box<Node>, Box<Node>),
+    Sqrt(Box<Node>),
+    // Index, radicand
+    Root(Box<Node>, Box<Node>),
+    Accent(Box<Node>, String),
+    Fenced {
+        open: String,
+        body: Box<Node>,
+        close: Option<String>,
+    },
+    Styled(FontStyle, Box<Node>),
+    // Sums, products and integrals with their optional limits
+    LargeOp {
+        symbol: String,
+        from: Option<Box<Node>>,
+        to: Option<Box<Node>>,
+        body: Option<Box<Node>>,
+    },
+    // Rows of cells
+    Matrix(Vec<Vec<Node>>),
+}
+
+// Font attributes switched by `bold`, `nbold`, `ital` and `nitalic`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
+pub(crate) enum FontStyle {
+    Bold,
+    NotBold,
+    Italic,
+    NotItalic,
+}
+
+// Font requested by the enclosing style keywords, None meaning the token's own default
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Font {
+    pub(crate) bold: Option<bool>,
+    pub(crate) italic: Option<bool>,
+}
+
+impl Font {
+    pub(crate) fn with(self, style: FontStyle) -> Font {
+        match style {
+            FontStyle::Bold => Font {
+                bold: Some(true),
+                ..self
+            },
+            FontStyle::NotBold => Font {
+                bold: Some(false),
+                ..self
+            },
+            FontStyle::Italic => Font {
+                italic: Some(true),
+                ..self
+            },
+            FontStyle::NotItalic => Font {
+                italic: Some(false),
+                ..self
+            },
+        }
+    }
+}
+
+impl Node {
+    pub(crate) fn operator(symbol: &str) -> Node {
+        Node::Operator {
+            symbol: symbol.to_string(),
+            kind: None,
+            precedence: precedence(symbol).unwrap_or(3),
+        }
+    }
+
+    // An empty row stands in for missing operands so script and fraction arities hold
+    pub(crate) fn empty() -> Node {
+        Node::Row(Vec::new())
+    }
+
+    // Whether linear notations can use the node as an operand without parentheses
+    pub(crate) fn is_atom(&self) -> bool {
+        match self {
+            Node::Number(text)
+            | Node::Identifier(text)
+            | Node::Function(text)
+            | Node::Text(text) => {
+                text.chars().count() == 1 || text.chars().all(char::is_alphanumeric)
+            }
+            Node::Fenced { .. } | Node::Matrix(_) => true,
+            Node::Row(children) => matches!(children.as_slice(), [child] if child.is_atom()),
+            Node::Styled(_, body) => body.is_atom(),
+            _ => false,
+        }
+    }
+}
+
+// Integrals take their limits as scripts, other large operators above and below
+pub(crate) fn is_integral(symbol: &str) -> bool {
+    matches!(symbol, "∫" | "∬" | "∭" | "∮")
+}
+
+// Precedence of the built-in operators, on the same scale as the registry's
+pub(crate) fn precedence(symbol: &str) -> Option<u8> {
+    match symbol {
+        "=" | "<" | ">" | "<=" | ">=" | "<>" | "≤" | "≥" | "≠" | "≈" | "≡" | "∼" | "∝" => {
+            Some(1)
+        }
+        "+" | "-" | "−" | "±" | "∓" | "+-" | "-+" => Some(2),
+        "×" | "*" | "·" | "÷" | "/" | "∘" => Some(3),
+        // Juxtaposition binds tighter than any written operator
+        INVISIBLE_TIMES => Some(4),
+        _ => None,
+    }
+}
@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionOptions, Diagnostic, DiagnosticSeverity,
+    DidChangeTextDocumentParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams,
+    DocumentFormattingParams, Hover, HoverContents, HoverParams, HoverProviderCapability,
+    MarkupContent, MarkupKind, NumberOrString, OneOf, Position, PublishDiagnosticsParams, Range,
+    ServerCapabilities, TextDocumentSyncCapability, TextDocumentSyncKind, TextEdit, Uri,
+};
+
+use serde_json::Value;
+use sm2mml::{Severity, format_starmath, keywords, lint, starmath_to_mathml, starmath_to_unicode};
+
+// StarMath language server over stdio
+fn main() -> Result<()> {
+    let (connection, io_threads) = Connection::stdio();
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec!["%".to_string()]),
+            ..CompletionOptions::default()
+        }),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        document_formatting_provider: Some(OneOf::Left(true)),
+        ..ServerCapabilities::default()
+    };
+    connection.initialize(serde_json::to_value(capabilities)?)?;
+
+    let mut server = Server {
+        connection,
+        documents: HashMap::new(),
+    };
+    server.run()?;
+    // The writer thread only stops once the connection is gone
+    drop(server);
+    io_threads.join()?;
+    Ok(())
+}
+
+struct Server {
+    connection: Connection,
+    documents: HashMap<Uri, String>,
+}
+
+impl Server {
+    fn run(&mut self) -> Result<()> {
+        let receiver = self.connection.receiver.clone();
+        for message in &receiver {
+            match message {
+                Message::Request(request) => {
+                    if self.connection.handle_shutdown(&request)? {
+                        return Ok(());
+                    }
+                    self.handle_request(request)?;
+                }
+                Message::Notification(notification) => self.handle_notification(notification)?,
+                Message::Response(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_request(&mut self, request: Request) -> Result<()> {
+        let response = match self.respond(&request.method, request.params) {
+            Ok(Some(result)) => Response::new_ok(request.id, result),
+            Ok(None) => Response::new_err(
+                request.id,
+                ErrorCode::MethodNotFound as i32,
+                format!("Unsupported request {}", request.method),
+            ),
+            // Malformed parameters fail this request only, the server keeps serving
+            Err(error) => Response::new_err(
+                request.id,
+                ErrorCode::InvalidParams as i32,
+                error.to_string(),
+            ),
+        };
+        self.connection.sender.send(response.into())?;
+        Ok(())
+    }
+
+    // Result of a request, None when the method is not supported
+    fn respond(&self, method: &str, params: Value) -> serde_json::Result<Option<Value>> {
+        let result = match method {
+            "textDocument/completion" => serde_json::to_value(completions())?,
+            "textDocument/hover" => {
+                let params: HoverParams = serde_json::from_value(params)?;
+                let uri = params.text_document_position_params.text_document.uri;
+                serde_json::to_value(self.documents.get(&uri).and_then(|text| hover(text)))?
+            }
+            "textDocument/formatting" => {
+                let params: DocumentFormattingParams = serde_json::from_value(params)?;
+                let edits = self
+                    .documents
+                    .get(&params.text_document.uri)
+                    .and_then(|text| format(text));
+                serde_json::to_value(edits)?
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(result))
+    }
+
+    // Applies a document notification, returning the document whose diagnostics changed
+    fn update(&mut self, method: &str, params: Value) -> serde_json::Result<Option<Uri>> {
+        let uri = match method {
+            "textDocument/didOpen" => {
+                let params: DidOpenTextDocumentParams = serde_json::from_value(params)?;
+                let uri = params.text_document.uri;
+                self.documents
+                    .insert(uri.clone(), params.text_document.text);
+                uri
+            }
+            "textDocument/didChange" => {
+                let params: DidChangeTextDocumentParams = serde_json::from_value(params)?;
+                let uri = params.text_document.uri;
+                // Full synchronization, the last change holds the whole text
+                if let Some(change) = params.content_changes.into_iter().last() {
+                    self.documents.insert(uri.clone(), change.text);
+                }
+                uri
+            }
+            "textDocument/didClose" => {
+                let params: DidCloseTextDocumentParams = serde_json::from_value(params)?;
+                self.documents.remove(&params.text_document.uri);
+                return Ok(None);
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(uri))
+    }
+
+    fn handle_notification(&mut self, notification: Notification) -> Result<()> {
+        let uri = match self.update(&notification.method, notification.params) {
+            Ok(Some(uri)) => uri,
+            Ok(None) => return Ok(()),
+            // Notifications get no reply, a malformed one is logged and dropped
+            Err(error) => {
+                eprintln!("Ignoring {}: {}", notification.method, error);
+                return Ok(());
+            }
+        };
+
+        let diagnostics = self.documents.get(&uri).map(|text| diagnostics(text));
+        let params = PublishDiagnosticsParams {
+            uri,
+            diagnostics: diagnostics.unwrap_or_default(),
+            version: None,
+        };
+        let notification = Notification::new(
+            "textDocument/publishDiagnostics".to_string(),
+            serde_json::to_value(params)?,
+        );
+        self.connection.sender.send(notification.into())?;
+        Ok(())
+    }
+}
+
+fn diagnostics(text: &str) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<_> = lint(text)
+        .into_iter()
+        .map(|diagnostic| Diagnostic {
+            range: Range::new(
+                position(text, diagnostic.span.start),
+                position(text, diagnostic.span.end),
+            ),
+            severity: Some(match diagnostic.severity {
+                Severity::Warning => DiagnosticSeverity::WARNING,
+                Severity::Error => DiagnosticSeverity::ERROR,
+            }),
+            code: Some(NumberOrString::String(diagnostic.code.to_string())),
+            source: Some("sm2mml".to_string()),
+            message: diagnostic.message,
+            ..Diagnostic::default()
+        })
+        .collect();
+    // Conversion errors carry no location, they are shown on the whole document. Text the
+    // linter already rejects is left unconverted.
+    if !has_errors(text)
+        && let Err(error) = starmath_to_mathml(text.trim())
+    {
+        diagnostics.push(Diagnostic {
+            range: Range::new(Position::new(0, 0), position(text, text.len())),
+            severity: Some(DiagnosticSeverity::ERROR),
+            source: Some("sm2mml".to_string()),
+            message: error.to_string(),
+            ..Diagnostic::default()
+        });
+    }
+    diagnostics
+}
+
+// Whether the linter finds structural errors such as unbalanced braces or fences
+fn has_errors(text: &str) -> bool {
+    lint(text)
+        .iter()
+        .any(|diagnostic| diagnostic.severity == Severity::Error)
+}
+
+fn completions() -> Vec<CompletionItem> {
+    keywords()
+        .into_iter()
+        .map(|keyword| {
+            // Symbols are previewed as the character they stand for
+            let detail = keyword
+                .starts_with('%')
+                .then(|| starmath_to_unicode(&keyword).ok())
+                .flatten();
+            CompletionItem {
+                kind: Some(CompletionItemKind::KEYWORD),
+                detail,
+                label: keyword,
+                ..CompletionItem::default()
+            }
+        })
+        .collect()
+}
+
+fn hover(text: &str) -> Option<Hover> {
+    if has_errors(text) {
+        return None;
+    }
+    let preview = starmath_to_unicode(text.trim()).ok()?;
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: format!("`{}`", preview),
+        }),
+        range: None,
+    })
+}
+
+fn format(text: &str) -> Option<Vec<TextEdit>> {
+    let formatted = format_starmath(text.trim()).ok()? + "\n";
+    if formatted == text {
+        return Some(Vec::new());
+    }
+    let range = Range::new(Position::new(0, 0), position(text, text.len()));
+    Some(vec![TextEdit::new(range, formatted)])
+}
+
+// LSP position of a byte offset, whose character counts UTF-16 code units
+fn position(text: &str, offset: usize) -> Position {
+    let before = &text[..offset];
+    let line = before.matches('\n').count();
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    let character: usize = before[line_start..].chars().map(char::len_utf16).sum();
+    Position::new(line as u32, character as u32)
+}
@@ -0,0 +1,246 @@
+use anyhow::{Result, bail};
+
+use crate::parser::{KEYWORDS, Token, tokenize};
+use crate::registry::FUNCTIONS;
+use crate::symbols::greek_name;
+
+#[derive(Debug, Clone, Default)]
+pub struct FormatOptions {
+    /// Writes Greek letters typed as Unicode with their keyword, like `%alpha` for `α`.
+    pub greek_names: bool,
+}
+
+/// Re-emits StarMath in a canonical style: one space between tokens, lowercase keywords and
+/// no braces around single tokens.
+pub fn format_starmath(starmath: &str) -> Result<String> {
+    format_starmath_with_options(starmath, &FormatOptions::default())
+}
+
+pub fn format_starmath_with_options(starmath: &str, options: &FormatOptions) -> Result<String> {
+    let mut tokens = tokenize(starmath).into_iter();
+    let items = read_items(&mut tokens, false)?;
+    let items = simplify(items);
+    let mut words = Vec::new();
+    write_items(&items, options, &mut words);
+    Ok(words.join(" "))
+}
+
+#[derive(Debug)]
+enum Item {
+    Token(Token),
+    Group(Vec<Item>),
+}
+
+fn read_items(tokens: &mut impl Iterator<Item = Token>, nested: bool) -> Result<Vec<Item>> {
+    let mut items = Vec::new();
+    while let Some(token) = tokens.next() {
+        match token {
+            Token::LBrace => items.push(Item::Group(read_items(tokens, true)?)),
+            Token::RBrace if nested => return Ok(items),
+            Token::RBrace => bail!("Unmatched closing brace"),
+            token => items.push(Item::Token(token)),
+        }
+    }
+    if nested {
+        bail!("Unclosed brace");
+    }
+    Ok(items)
+}
+
+// Drops braces that group nothing but a single token or another group
+fn simplify(items: Vec<Item>) -> Vec<Item> {
+    let mut simplified = Vec::with_capacity(items.len());
+    for item in items {
+        let Item::Group(inner) = item else {
+            simplified.push(item);
+            continue;
+        };
+        let mut inner = simplify(inner);
+        // The braces after matrix belong to its syntax, and after left or right they take
+        // the place of the fence
+        let after_keyword = matches!(
+            simplified.last(),
+            Some(Item::Token(Token::Word(word))) if ["matrix", "left", "right"]
+                .iter()
+                .any(|keyword| word.eq_ignore_ascii_case(keyword))
+        );
+        if !after_keyword && inner.len() == 1 {
+            match inner.pop() {
+                Some(Item::Group(group)) => {
+                    simplified.push(Item::Group(group));
+                    continue;
+                }
+                Some(Item::Token(token)) if is_plain(&token) => {
+                    simplified.push(Item::Token(token));
+                    continue;
+                }
+                Some(item) => inner.push(item),
+                None => {}
+            }
+        }
+        simplified.push(Item::Group(inner));
+    }
+    simplified
+}
+
+// A token that stands on its own as an operand, so braces around it change nothing
+pub(crate) fn is_plain(token: &Token) -> bool {
+    match token {
+        // Text takes no script or over of its own, the braces around it are what does
+        Token::String(_) => false,
+        Token::Word(word) => {
+            word.chars()
+                .all(|c| c.is_alphanumeric() || c == '%' || c == ',' || c == '.')
+                && word.chars().any(char::is_alphanumeric)
+                && canonical_keyword(word).is_none()
+        }
+        _ => false,
+    }
+}
+
+pub(crate) fn canonical_keyword(word: &str) -> Option<&'static str> {
+    KEYWORDS
+        .iter()
+        .chain(FUNCTIONS)
+        .find(|keyword| keyword.eq_ignore_ascii_case(word))
+        .copied()
+}
+
+fn write_items(items: &[Item], options: &FormatOptions, words: &mut Vec<String>) {
+    for item in items {
+        match item {
+            Item::Group(inner) => {
+                words.push("{".to_string());
+                write_items(inner, options, words);
+                words.push("}".to_string());
+            }
+            Item::Token(token) => words.push(write_token(token, options)),
+        }
+    }
+}
+
+fn write_token(token: &Token, options: &FormatOptions) -> String {
+    match token {
+        Token::Word(word) => {
+            if let Some(keyword) = canonical_keyword(word) {
+                return keyword.to_string();
+            }
+            if options.greek_names
+                && let Some(name) = greek_name(word)
+            {
+                return format!("%{}", name);
+            }
+            word.clone()
+        }
+        Token::String(text) => format!("\"{}\"", text),
+        Token::LParen => "(".to_string(),
+        Token::RParen => ")".to_string(),
+        Token::LBrace => "{".to_string(),
+        Token::RBrace => "}".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Options;
+    use crate::ast::Node;
+    use crate::parser::parse;
+
+    // A row of one node renders as that node, which is all the braces around a token add
+    fn unwrap_rows(node: Node) -> Node {
+        let unwrap = |node: Box<Node>| Box::new(unwrap_rows(*node));
+        match node {
+            Node::Row(children) if children.len() == 1 => {
+                unwrap_rows(children.into_iter().next().unwrap())
+            }
+            Node::Row(children) => Node::Row(children.into_iter().map(unwrap_rows).collect()),
+            Node::Sub(base, script) => Node::Sub(unwrap(base), unwrap(script)),
+            Node::Sup(base, script) => Node::Sup(unwrap(base), unwrap(script)),
+            Node::Frac(num, den) => Node::Frac(unwrap(num), unwrap(den)),
+            Node::Sqrt(body) => Node::Sqrt(unwrap(body)),
+            Node::Root(index, body) => Node::Root(unwrap(index), unwrap(body)),
+            Node::Accent(base, accent) => Node::Accent(unwrap(base), accent),
+            Node::Fenced { open, body, close } => Node::Fenced {
+                open,
+                body: unwrap(body),
+                close,
+            },
+            Node::Styled(style, body) => Node::Styled(style, unwrap(body)),
+            Node::LargeOp {
+                symbol,
+                from,
+                to,
+                body,
+            } => Node::LargeOp {
+                symbol,
+                from: from.map(unwrap),
+                to: to.map(unwrap),
+                body: body.map(unwrap),
+            },
+            Node::Matrix(rows) => Node::Matrix(
+                rows.into_iter()
+                    .map(|row| row.into_iter().map(unwrap_rows).collect())
+                    .collect(),
+            ),
+            node => node,
+        }
+    }
+
+    // The MathML of a formula, leaving out the annotation holding its source
+    fn presentation(starmath: &str) -> String {
+        let mathml = crate::starmath_to_mathml(starmath).unwrap();
+        mathml[..mathml.find("<annotation").unwrap()].to_string()
+    }
+
+    #[test]
+    fn formatting_keeps_the_mathml() {
+        let formulas = [
+            "left{lbrace%}",
+            "left { a } b right )",
+            "left ( a right {)}",
+            "matrix {a}",
+            "{a+b} rsub {2}",
+            "x^{2} over {y}",
+        ];
+        for formula in formulas {
+            let formatted = format_starmath(formula).unwrap();
+            assert_eq!(
+                presentation(&formatted),
+                presentation(formula),
+                "{} formatted as {}",
+                formula,
+                formatted
+            );
+        }
+    }
+
+    #[test]
+    fn formatting_keeps_the_parse() {
+        let formulas = [
+            "{\"t\"} over x",
+            "{a} over {b}",
+            "{{a + b}} over {c}",
+            "{a} rsub 2",
+            "x ^ {2}",
+            "sqrt {x}",
+            "sum from {i = 1} to {n} {i}",
+            "left ( {a} over b right )",
+            "matrix { a # b ## c # d }",
+            "{%alpha} + {3.5}",
+            "nroot {3} {x}",
+            "bold {x} + {\"text\"}",
+        ];
+        let options = Options::default();
+        for formula in formulas {
+            let formatted = format_starmath(formula).unwrap();
+            assert_eq!(
+                unwrap_rows(parse(&formatted, &options).unwrap()),
+                unwrap_rows(parse(formula, &options).unwrap()),
+                "{} formatted as {}",
+                formula,
+                formatted
+            );
+        }
+    }
+}
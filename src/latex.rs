@@ -0,0 +1,680 @@
+use anyhow::{Result, bail};
+use std::iter::Peekable;
+use std::str::Chars;
+
+use crate::ast::{FontStyle, Node, precedence};
+use crate::symbols::{greek_letter, greek_name};
+use crate::{INVISIBLE_TIMES, Options, parser};
+
+// LaTeX symbols without a StarMath keyword, written as their Unicode character
+const SYMBOLS: &[(&str, &str)] = &[
+    ("cdot", "·"),
+    ("div", "÷"),
+    ("circ", "∘"),
+    ("mp", "-+"),
+    ("leq", "<="),
+    ("le", "<="),
+    ("geq", ">="),
+    ("ge", ">="),
+    ("neq", "<>"),
+    ("ne", "<>"),
+    ("approx", "≈"),
+    ("equiv", "≡"),
+    ("sim", "∼"),
+    ("propto", "∝"),
+    ("infty", "∞"),
+    ("partial", "∂"),
+    ("nabla", "∇"),
+    ("to", "→"),
+    ("rightarrow", "→"),
+    ("leftarrow", "←"),
+    ("Rightarrow", "⇒"),
+    ("Leftrightarrow", "⇔"),
+    ("in", "∈"),
+    ("notin", "∉"),
+    ("subset", "⊂"),
+    ("subseteq", "⊆"),
+    ("cup", "∪"),
+    ("cap", "∩"),
+    ("forall", "∀"),
+    ("exists", "∃"),
+    ("ldots", "…"),
+    ("dots", "…"),
+    ("cdots", "⋯"),
+];
+
+const FUNCTIONS: &[&str] = &[
+    "sin", "cos", "tan", "sec", "csc", "cot", "sinh", "cosh", "tanh", "coth", "arcsin", "arccos",
+    "arctan", "log", "ln", "lg", "exp", "lim", "sup", "inf", "max", "min", "det", "dim", "ker",
+    "deg", "gcd", "hom", "arg", "Pr",
+];
+
+const LARGE_OPERATORS: &[(&str, &str)] = &[
+    ("sum", "sum"),
+    ("prod", "prod"),
+    ("coprod", "coprod"),
+    ("int", "int"),
+    ("iint", "iint"),
+    ("iiint", "iiint"),
+    ("oint", "lint"),
+];
+
+// Commands that only affect spacing or layout, which StarMath leaves to the renderer
+const IGNORED: &[&str] = &[
+    ",",
+    ";",
+    ":",
+    "!",
+    " ",
+    "quad",
+    "qquad",
+    "limits",
+    "nolimits",
+    "displaystyle",
+    "textstyle",
+];
+
+/// Converts a LaTeX math expression to StarMath 5.0, like `\frac{a}{b}` to `{ { a } over { b } }`.
+pub fn latex_to_starmath(latex: &str) -> Result<String> {
+    let tokens = tokenize(latex)?;
+    let mut converter = Converter { tokens, pos: 0 };
+    let starmath = converter.convert_sequence(&[])?;
+    if let Some(token) = converter.peek() {
+        bail!("Unexpected {} in LaTeX input", token.describe());
+    }
+    Ok(starmath)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    // \name, or \ followed by a single non-letter
+    Command(String),
+    Number(String),
+    Char(char),
+    LBrace,
+    RBrace,
+    Sub,
+    Sup,
+    // & between matrix cells
+    Align,
+    // \\ between matrix rows
+    NewLine,
+    // Verbatim argument of \text or \begin, braces included
+    Text(String),
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Command(name) => format!("\\{}", name),
+            Token::Number(number) => number.clone(),
+            Token::Char(ch) => ch.to_string(),
+            Token::LBrace => "{".to_string(),
+            Token::RBrace => "}".to_string(),
+            Token::Sub => "_".to_string(),
+            Token::Sup => "^".to_string(),
+            Token::Align => "&".to_string(),
+            Token::NewLine => "\\\\".to_string(),
+            Token::Text(text) => format!("{{{}}}", text),
+        }
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+    while let Some(ch) = chars.next() {
+        let token = match ch {
+            c if c.is_whitespace() => continue,
+            '%' => {
+                // Comment up to the end of the line
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+                continue;
+            }
+            '\\' => {
+                let command = read_command(&mut chars)?;
+                let verbatim = matches!(&command, Token::Command(name)
+                    if ["text", "textrm", "mbox", "begin", "end"].contains(&name.as_str()));
+                tokens.push(command);
+                if verbatim {
+                    tokens.push(read_verbatim(&mut chars)?);
+                }
+                continue;
+            }
+            '{' => Token::LBrace,
+            '}' => Token::RBrace,
+            '_' => Token::Sub,
+            '^' => Token::Sup,
+            '&' => Token::Align,
+            c if c.is_ascii_digit() || c == '.' => {
+                let mut number = c.to_string();
+                while let Some(&c) = chars.peek()
+                    && (c.is_ascii_digit() || c == '.')
+                {
+                    number.push(c);
+                    chars.next();
+                }
+                Token::Number(number)
+            }
+            c => Token::Char(c),
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+fn read_command(chars: &mut Peekable<Chars>) -> Result<Token> {
+    let Some(first) = chars.next() else {
+        bail!("LaTeX input ends with a backslash");
+    };
+    if first == '\\' {
+        return Ok(Token::NewLine);
+    }
+    let mut name = first.to_string();
+    if first.is_ascii_alphabetic() {
+        while let Some(&c) = chars.peek()
+            && c.is_ascii_alphabetic()
+        {
+            name.push(c);
+            chars.next();
+        }
+    }
+    Ok(Token::Command(name))
+}
+
+fn read_verbatim(chars: &mut Peekable<Chars>) -> Result<Token> {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+    if chars.next() != Some('{') {
+        bail!("Expected {{ after \\text, \\begin or \\end in LaTeX input");
+    }
+    let mut text = String::new();
+    let mut depth = 0;
+    for c in chars.by_ref() {
+        match c {
+            '{' => depth += 1,
+            '}' if depth == 0 => return Ok(Token::Text(text)),
+            '}' => depth -= 1,
+            _ => {}
+        }
+        text.push(c);
+    }
+    bail!("Unclosed group in LaTeX input")
+}
+
+struct Converter {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Converter {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<()> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            Some(token) => bail!(
+                "Expected {} but found {} in LaTeX input",
+                expected.describe(),
+                token.describe()
+            ),
+            None => bail!("Expected {} at end of LaTeX input", expected.describe()),
+        }
+    }
+
+    // Converts up to a closing brace, \right, \end, a matrix separator or the end of input
+    fn convert_sequence(&mut self, stop: &[Token]) -> Result<String> {
+        let mut parts = Vec::new();
+        while let Some(token) = self.peek() {
+            if matches!(token, Token::RBrace)
+                || stop.contains(token)
+                || matches!(token, Token::Command(name) if name == "right" || name == "end")
+            {
+                break;
+            }
+            // {a \over b} divides everything before it in the group by everything after
+            if matches!(token, Token::Command(name) if name == "over") {
+                self.next();
+                let denominator = self.convert_sequence(stop)?;
+                return Ok(format!(
+                    "{{ {{ {} }} over {{ {} }} }}",
+                    parts.join(" "),
+                    denominator
+                ));
+            }
+            let element = self.convert_scripted()?;
+            if !element.is_empty() {
+                parts.push(element);
+            }
+        }
+        Ok(parts.join(" "))
+    }
+
+    // An element followed by its subscript and superscript, in either order
+    fn convert_scripted(&mut self) -> Result<String> {
+        let (mut base, large) = self.convert_element()?;
+        let mut sub = None;
+        let mut sup = None;
+        loop {
+            match self.peek() {
+                Some(Token::Sub) if sub.is_none() => {
+                    self.next();
+                    sub = Some(self.convert_argument()?);
+                }
+                Some(Token::Sup) if sup.is_none() => {
+                    self.next();
+                    sup = Some(self.convert_argument()?);
+                }
+                Some(Token::Command(name)) if large && (name == "limits" || name == "nolimits") => {
+                    self.next();
+                }
+                _ => break,
+            }
+        }
+
+        if large {
+            // Large operators take their limits with from and to
+            if let Some(sub) = sub {
+                base = format!("{} from {{ {} }}", base, sub);
+            }
+            if let Some(sup) = sup {
+                base = format!("{} to {{ {} }}", base, sup);
+            }
+            return Ok(base);
+        }
+
+        if sub.is_none() && sup.is_none() {
+            return Ok(base);
+        }
+        if base.is_empty() {
+            // Scripts on nothing, like {}_a, still need a base
+            base = "{}".to_string();
+        } else if base.contains(' ') {
+            base = format!("{{ {} }}", base);
+        }
+        match (sub, sup) {
+            (Some(sub), Some(sup)) => {
+                // Grouped so the superscript applies to the whole subscripted base
+                Ok(format!("{{ {} rsub {{ {} }} }} ^ {{ {} }}", base, sub, sup))
+            }
+            (Some(sub), None) => Ok(format!("{} rsub {{ {} }}", base, sub)),
+            (None, Some(sup)) => Ok(format!("{} ^ {{ {} }}", base, sup)),
+            (None, None) => unreachable!(),
+        }
+    }
+
+    // A braced group or a single token, as taken by scripts and command arguments
+    fn convert_argument(&mut self) -> Result<String> {
+        if self.peek() == Some(&Token::LBrace) {
+            self.next();
+            let group = self.convert_sequence(&[])?;
+            self.expect(Token::RBrace)?;
+            return Ok(group);
+        }
+        // Without braces an argument is a single character, so \frac12 is one half
+        if let Some(Token::Number(number)) = self.peek()
+            && number.chars().count() > 1
+        {
+            let mut rest = number.clone();
+            let digit = rest.remove(0);
+            self.tokens[self.pos] = Token::Number(rest);
+            return Ok(digit.to_string());
+        }
+        let (element, _) = self.convert_element()?;
+        Ok(element)
+    }
+
+    // Returns the StarMath for the next element and whether it is a large operator
+    fn convert_element(&mut self) -> Result<(String, bool)> {
+        let Some(token) = self.next() else {
+            bail!("Unexpected end of LaTeX input");
+        };
+        let element = match token {
+            Token::Number(number) => number,
+            Token::Char(ch) => match ch {
+                '(' | '[' => {
+                    // Plain brackets would be dropped, StarMath needs them as fences
+                    let close = if ch == '(' { ')' } else { ']' };
+                    let body = self.convert_sequence(&[Token::Char(close)])?;
+                    match self.next() {
+                        Some(Token::Char(c)) if c == close => {
+                            format!("left {} {} right {}", ch, body, close)
+                        }
+                        _ => format!("left {} {} right none", ch, body),
+                    }
+                }
+                // Would open a string or separate matrix cells in StarMath
+                '"' | '#' => bail!("Unsupported character {} in LaTeX input", ch),
+                c => c.to_string(),
+            },
+            Token::LBrace => {
+                let group = self.convert_sequence(&[])?;
+                self.expect(Token::RBrace)?;
+                format!("{{ {} }}", group)
+            }
+            Token::Command(name) => return self.convert_command(&name),
+            token => bail!("Unexpected {} in LaTeX input", token.describe()),
+        };
+        Ok((element, false))
+    }
+
+    fn convert_command(&mut self, name: &str) -> Result<(String, bool)> {
+        if let Some((_, keyword)) = LARGE_OPERATORS.iter().find(|(latex, _)| *latex == name) {
+            return Ok((keyword.to_string(), true));
+        }
+        if greek_letter(name).is_some() {
+            return Ok((format!("%{}", name), false));
+        }
+        if FUNCTIONS.contains(&name) {
+            return Ok((name.to_string(), false));
+        }
+        if let Some((_, symbol)) = SYMBOLS.iter().find(|(latex, _)| *latex == name) {
+            return Ok((symbol.to_string(), false));
+        }
+        if IGNORED.contains(&name) {
+            return Ok((String::new(), false));
+        }
+
+        let element = match name {
+            "frac" | "dfrac" | "tfrac" => {
+                let num = self.convert_argument()?;
+                let den = self.convert_argument()?;
+                // Braced so "over" cannot take in the rest of an enclosing group
+                format!("{{ {{ {} }} over {{ {} }} }}", num, den)
+            }
+            "sqrt" => {
+                if self.peek() == Some(&Token::Char('[')) {
+                    self.next();
+                    let index = self.convert_sequence(&[Token::Char(']')])?;
+                    self.expect(Token::Char(']'))?;
+                    let body = self.convert_argument()?;
+                    format!("nroot {{ {} }} {{ {} }}", index, body)
+                } else {
+                    format!("sqrt {{ {} }}", self.convert_argument()?)
+                }
+            }
+            "left" => {
+                let open = self.convert_delimiter(true)?;
+                let body = self.convert_sequence(&[])?;
+                match self.next() {
+                    Some(Token::Command(name)) if name == "right" => {}
+                    _ => bail!("\\left without a matching \\right in LaTeX input"),
+                }
+                let close = self.convert_delimiter(false)?;
+                format!("left {} {} right {}", open, body, close)
+            }
+            "begin" => self.convert_environment()?,
+            "mathbf" | "boldsymbol" => format!("bold {{ {} }}", self.convert_argument()?),
+            "mathit" => format!("ital {{ {} }}", self.convert_argument()?),
+            "mathrm" | "operatorname" => format!("nitalic {{ {} }}", self.convert_argument()?),
+            // StarMath strings cannot contain double quotes
+            "text" | "textrm" | "mbox" => format!("\"{}\"", self.read_text()?.replace('"', "'")),
+            "times" => "times".to_string(),
+            "pm" => "+-".to_string(),
+            "%" => "\"%\"".to_string(),
+            "{" => "\"{\"".to_string(),
+            "}" => "\"}\"".to_string(),
+            "|" => "‖".to_string(),
+            "acute" => format!("acute {{ {} }}", self.convert_argument()?),
+            _ => bail!("Unsupported LaTeX command \\{}", name),
+        };
+        Ok((element, false))
+    }
+
+    // Delimiter after \left or \right, as a StarMath fence
+    fn convert_delimiter(&mut self, left: bool) -> Result<String> {
+        let fence = match self.next() {
+            Some(Token::Char(c)) if "()[]".contains(c) => c.to_string(),
+            Some(Token::Char('|')) => if left { "lline" } else { "rline" }.to_string(),
+            Some(Token::Number(dot)) if dot == "." => "none".to_string(),
+            Some(Token::Command(name)) => match name.as_str() {
+                "{" | "lbrace" => "lbrace".to_string(),
+                "}" | "rbrace" => "rbrace".to_string(),
+                "langle" => "langle".to_string(),
+                "rangle" => "rangle".to_string(),
+                "|" => if left { "ldline" } else { "rdline" }.to_string(),
+                _ => bail!("Unsupported delimiter \\{} in LaTeX input", name),
+            },
+            Some(token) => bail!("Unsupported delimiter {} in LaTeX input", token.describe()),
+            None => bail!("Missing delimiter at end of LaTeX input"),
+        };
+        Ok(fence)
+    }
+
+    fn convert_environment(&mut self) -> Result<String> {
+        let name = self.read_text()?;
+        let (open, close) = match name.as_str() {
+            "matrix" => (None, None),
+            "pmatrix" => (Some("("), Some(")")),
+            "bmatrix" => (Some("["), Some("]")),
+            "Bmatrix" => (Some("lbrace"), Some("rbrace")),
+            "vmatrix" => (Some("lline"), Some("rline")),
+            "Vmatrix" => (Some("ldline"), Some("rdline")),
+            _ => bail!("Unsupported LaTeX environment {}", name),
+        };
+
+        let mut rows = Vec::new();
+        let mut cells = Vec::new();
+        loop {
+            cells.push(self.convert_sequence(&[Token::Align, Token::NewLine])?);
+            match self.next() {
+                Some(Token::Align) => {}
+                Some(Token::NewLine) => {
+                    rows.push(std::mem::take(&mut cells).join(" # "));
+                }
+                Some(Token::Command(end)) if end == "end" => break,
+                _ => bail!("Environment {} is not closed in LaTeX input", name),
+            }
+        }
+        // A trailing \\ before \end leaves an empty row behind
+        if cells.len() > 1 || !cells[0].is_empty() {
+            rows.push(cells.join(" # "));
+        }
+        let end = self.read_text()?;
+        if end != name {
+            bail!(
+                "\\begin{{{}}} ended by \\end{{{}}} in LaTeX input",
+                name,
+                end
+            );
+        }
+
+        let matrix = format!("matrix {{ {} }}", rows.join(" ## "));
+        Ok(match (open, close) {
+            (Some(open), Some(close)) => format!("left {} {} right {}", open, matrix, close),
+            _ => matrix,
+        })
+    }
+
+    // Argument read verbatim by the tokenizer
+    fn read_text(&mut self) -> Result<String> {
+        match self.next() {
+            Some(Token::Text(text)) => Ok(text),
+            _ => bail!("Expected a braced argument in LaTeX input"),
+        }
+    }
+}
+
+/// Converts StarMath to LaTeX math, like `{a} over {b}` to `\frac{a}{b}`.
+pub fn starmath_to_latex(starmath: &str) -> Result<String> {
+    starmath_to_latex_with_options(starmath, &Options::default())
+}
+
+pub fn starmath_to_latex_with_options(starmath: &str, options: &Options) -> Result<String> {
+    let root = parser::parse(starmath, options)?;
+    Ok(write(&root))
+}
+
+fn write(node: &Node) -> String {
+    match node {
+        Node::Number(number) => number.clone(),
+        Node::Identifier(name) if precedence(name).is_some() => write_operator(name),
+        Node::Identifier(name) => match greek_name(name) {
+            Some(greek) => format!("\\{}", greek),
+            None if name.chars().count() > 1 && name.chars().all(char::is_alphabetic) => {
+                format!("\\mathit{{{}}}", name)
+            }
+            None => escape(name),
+        },
+        Node::Function(name) if FUNCTIONS.contains(&name.as_str()) => format!("\\{}", name),
+        Node::Function(name) => format!("\\operatorname{{{}}}", name),
+        Node::Operator { symbol, .. } => write_operator(symbol),
+        Node::Text(text) => format!("\\text{{{}}}", escape(text)),
+        Node::Row(children) => children
+            .iter()
+            .map(write)
+            .filter(|written| !written.is_empty())
+            .collect::<Vec<_>>()
+            .join(" "),
+        Node::Sub(base, sub) => format!("{}_{{{}}}", write_base(base), write(sub)),
+        Node::Sup(base, sup) => format!("{}^{{{}}}", write_base(base), write(sup)),
+        Node::Frac(num, den) => format!("\\frac{{{}}}{{{}}}", write(num), write(den)),
+        Node::Sqrt(body) => format!("\\sqrt{{{}}}", write(body)),
+        Node::Root(index, body) => format!("\\sqrt[{}]{{{}}}", write(index), write(body)),
+        Node::Accent(base, accent) => match accent.as_str() {
+            "´" => format!("\\acute{{{}}}", write(base)),
+            _ => format!("\\overset{{{}}}{{{}}}", accent, write(base)),
+        },
+        Node::Fenced { open, body, close } => {
+            let close = close.as_deref().unwrap_or("");
+            if let Node::Row(children) = body.as_ref()
+                && let [Node::Matrix(rows)] = children.as_slice()
+            {
+                let environment = match (open.as_str(), close) {
+                    ("(", ")") => Some("pmatrix"),
+                    ("[", "]") => Some("bmatrix"),
+                    ("{", "}") => Some("Bmatrix"),
+                    ("|", "|") => Some("vmatrix"),
+                    ("‖", "‖") => Some("Vmatrix"),
+                    _ => None,
+                };
+                if let Some(environment) = environment {
+                    return write_matrix(rows, environment);
+                }
+            }
+            format!(
+                "\\left{} {} \\right{}",
+                write_delimiter(open),
+                write(body),
+                write_delimiter(close)
+            )
+        }
+        Node::Styled(style, body) => match style {
+            FontStyle::Bold => format!("\\mathbf{{{}}}", write(body)),
+            FontStyle::Italic => format!("\\mathit{{{}}}", write(body)),
+            FontStyle::NotItalic => format!("\\mathrm{{{}}}", write(body)),
+            FontStyle::NotBold => write(body),
+        },
+        Node::LargeOp {
+            symbol,
+            from,
+            to,
+            body,
+        } => {
+            let mut written = write_operator(symbol);
+            if let Some(from) = from {
+                written.push_str(&format!("_{{{}}}", write(from)));
+            }
+            if let Some(to) = to {
+                written.push_str(&format!("^{{{}}}", write(to)));
+            }
+            if let Some(body) = body {
+                written.push(' ');
+                written.push_str(&write(body));
+            }
+            written
+        }
+        Node::Matrix(rows) => write_matrix(rows, "matrix"),
+    }
+}
+
+// Scripts attach to the last token only, so longer bases are braced
+fn write_base(base: &Node) -> String {
+    let written = write(base);
+    if base.is_atom() && !written.contains(' ') {
+        written
+    } else {
+        format!("{{{}}}", written)
+    }
+}
+
+fn write_matrix(rows: &[Vec<Node>], environment: &str) -> String {
+    let rows: Vec<_> = rows
+        .iter()
+        .map(|row| row.iter().map(write).collect::<Vec<_>>().join(" & "))
+        .collect();
+    format!(
+        "\\begin{{{0}}} {1} \\end{{{0}}}",
+        environment,
+        rows.join(" \\\\ ")
+    )
+}
+
+fn write_operator(symbol: &str) -> String {
+    let command = match symbol {
+        INVISIBLE_TIMES => return String::new(),
+        "×" => "\\times",
+        "·" => "\\cdot",
+        "÷" => "\\div",
+        "−" => "-",
+        "±" | "+-" => "\\pm",
+        "∓" | "-+" => "\\mp",
+        "≤" | "<=" => "\\leq",
+        "≥" | ">=" => "\\geq",
+        "≠" | "<>" => "\\neq",
+        "∘" => "\\circ",
+        "≈" => "\\approx",
+        "≡" => "\\equiv",
+        "∼" => "\\sim",
+        "∝" => "\\propto",
+        "∑" => "\\sum",
+        "∏" => "\\prod",
+        "∐" => "\\coprod",
+        "∫" => "\\int",
+        "∬" => "\\iint",
+        "∭" => "\\iiint",
+        "∮" => "\\oint",
+        _ => return escape(symbol),
+    };
+    command.to_string()
+}
+
+fn write_delimiter(fence: &str) -> String {
+    let delimiter = match fence {
+        "" => ".",
+        "{" => "\\{",
+        "}" => "\\}",
+        "⟨" => "\\langle",
+        "⟩" => "\\rangle",
+        "‖" => "\\|",
+        _ => fence,
+    };
+    delimiter.to_string()
+}
+
+// Characters LaTeX reserves for its own syntax
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for ch in text.chars() {
+        match ch {
+            '#' | '$' | '%' | '&' | '_' | '{' | '}' => {
+                escaped.push('\\');
+                escaped.push(ch);
+            }
+            '\\' => escaped.push_str("\\backslash "),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
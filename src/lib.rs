@@ -1,127 +1,164 @@
 use anyhow::Result;
-use quick_xml::Writer;
 use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event};
+use quick_xml::{Reader, Writer};
 use std::io::Cursor;
 use xmlformat::Formatter;
 
+use mathml::MathmlWriter;
+
+mod alphanumeric;
+mod ast;
+mod format;
+mod latex;
+mod linear;
+mod lint;
+mod mathml;
+mod omml;
+mod parser;
+mod registry;
+mod symbols;
+mod typst;
+mod validate;
+
+pub mod odf;
+
+pub use format::{FormatOptions, format_starmath, format_starmath_with_options};
+pub use latex::{latex_to_starmath, starmath_to_latex, starmath_to_latex_with_options};
+pub use linear::{
+    starmath_to_asciimath, starmath_to_asciimath_with_options, starmath_to_unicode,
+    starmath_to_unicode_with_options,
+};
+pub use lint::{Diagnostic, Fix, Severity, apply_fixes, lint};
+pub use omml::{starmath_to_omml, starmath_to_omml_with_options};
+pub use registry::{Operator, OperatorKind, Registry};
+pub use typst::{starmath_to_typst, starmath_to_typst_with_options};
+pub use validate::{Violation, validate, validate_with_profile};
+
+const INVISIBLE_TIMES: &str = "\u{2062}";
+
+/// How words made of several letters are turned into identifiers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IdentifierPolicy {
+    /// LibreOffice behaviour: `abc` is a single italic identifier.
+    #[default]
+    Word,
+    /// `abc` is split into the implicitly multiplied identifiers `a`, `b` and `c`.
+    Letters,
+}
+
+/// Flavour of MathML to produce.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Profile {
+    /// Full MathML 3, as written by LibreOffice.
+    #[default]
+    MathMl3,
+    /// The subset implemented by browsers, styling through Unicode characters instead of
+    /// `mathvariant`.
+    Core,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    pub identifiers: IdentifierPolicy,
+    pub registry: Registry,
+    /// Inserts an invisible times operator between juxtaposed operands, as in `2 x`.
+    pub invisible_times: bool,
+    pub profile: Profile,
+    /// Namespace prefix for the MathML elements, like `math` for `<math:mrow>`.
+    pub prefix: Option<String>,
+    /// Marks the formula as part of running text with `display="inline"`.
+    pub inline: bool,
+}
+
+/// Words with a meaning of their own in StarMath: keywords, standard functions and `%`
+/// symbols, as offered for completion.
+pub fn keywords() -> Vec<String> {
+    let symbols = symbols::GREEK.iter().map(|(name, _)| format!("%{}", name));
+    parser::KEYWORDS
+        .iter()
+        .chain(registry::FUNCTIONS)
+        .map(|keyword| keyword.to_string())
+        .chain(symbols)
+        .collect()
+}
+
 pub fn starmath_to_mathml(starmath: &str) -> Result<String> {
+    starmath_to_mathml_with_options(starmath, &Options::default())
+}
+
+pub fn starmath_to_mathml_with_options(starmath: &str, options: &Options) -> Result<String> {
+    let root = parser::parse(starmath, options)?;
+
     let mut writer = Writer::new(Cursor::new(Vec::new()));
 
     writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))?;
 
-    let mut math = BytesStart::new("math");
-    math.push_attribute(("xmlns", "http://www.w3.org/1998/Math/MathML"));
-    math.push_attribute(("display", "block"));
+    let prefix = options.prefix.as_deref();
+    let name = |local: &str| mathml::qualified_name(prefix, local);
+
+    let mut math = BytesStart::new(name("math"));
+    let xmlns = match prefix {
+        Some(prefix) => format!("xmlns:{}", prefix),
+        None => "xmlns".to_string(),
+    };
+    math.push_attribute((xmlns.as_str(), "http://www.w3.org/1998/Math/MathML"));
+    let display = if options.inline { "inline" } else { "block" };
+    math.push_attribute(("display", display));
     writer.write_event(Event::Start(math))?;
 
-    writer.write_event(Event::Start(BytesStart::new("semantics")))?;
-    writer.write_event(Event::Start(BytesStart::new("mrow")))?;
+    writer.write_event(Event::Start(BytesStart::new(name("semantics"))))?;
+    writer.write_event(Event::Start(BytesStart::new(name("mrow"))))?;
 
-    parse_starmath(&mut writer, starmath)?;
+    MathmlWriter::new(&mut writer, options.profile, prefix).write_children(&root)?;
 
-    writer.write_event(Event::End(BytesEnd::new("mrow")))?;
+    writer.write_event(Event::End(BytesEnd::new(name("mrow"))))?;
 
-    let mut annotation = BytesStart::new("annotation");
+    let mut annotation = BytesStart::new(name("annotation"));
     annotation.push_attribute(("encoding", "StarMath 5.0"));
     writer.write_event(Event::Start(annotation))?;
 
     let encoded = encode_html_entities(starmath);
     writer.write_event(Event::Text(BytesText::new("STARMATH")))?;
 
-    writer.write_event(Event::End(BytesEnd::new("annotation")))?;
-    writer.write_event(Event::End(BytesEnd::new("semantics")))?;
-    writer.write_event(Event::End(BytesEnd::new("math")))?;
+    writer.write_event(Event::End(BytesEnd::new(name("annotation"))))?;
+    writer.write_event(Event::End(BytesEnd::new(name("semantics"))))?;
+    writer.write_event(Event::End(BytesEnd::new(name("math"))))?;
 
     let buffer = writer.into_inner().into_inner();
     let xml_str = String::from_utf8(buffer)?;
     let formatted_xml = Formatter::default().format_xml(&xml_str)?;
-    let result = formatted_xml.replace("STARMATH", &encoded);
+    // Written as a character reference so the operator stays visible in the source
+    let result = formatted_xml
+        .replace(INVISIBLE_TIMES, "&#x2062;")
+        .replace("STARMATH", &encoded);
     Ok(result)
 }
 
-fn parse_starmath(writer: &mut Writer<Cursor<Vec<u8>>>, input: &str) -> Result<()> {
-    let tokens = tokenize(input);
-    let mut parser = Parser::new(tokens);
-    parser.parse_expression(writer)?;
-    Ok(())
-}
-
-#[derive(Debug, Clone)]
-enum Token {
-    Word(String),
-    LBrace,
-    RBrace,
-    LParen,
-    RParen,
-    String(String),
-}
-
-fn tokenize(input: &str) -> Vec<Token> {
-    // Decode HTML entities first
-    let decoded = decode_html_entities(input);
-
-    let mut tokens = Vec::new();
-    let mut chars = decoded.chars().peekable();
-
-    while let Some(&ch) = chars.peek() {
-        match ch {
-            ' ' | '\t' | '\n' => {
-                chars.next();
-            }
-            '{' => {
-                chars.next();
-                tokens.push(Token::LBrace);
-            }
-            '}' => {
-                chars.next();
-                tokens.push(Token::RBrace);
-            }
-            '(' => {
-                chars.next();
-                tokens.push(Token::LParen);
-            }
-            ')' => {
-                chars.next();
-                tokens.push(Token::RParen);
-            }
-            '"' => {
-                chars.next();
-                let mut string = String::new();
-                while let Some(&ch) = chars.peek() {
-                    if ch == '"' {
-                        chars.next();
-                        break;
-                    }
-                    string.push(chars.next().unwrap());
-                }
-                tokens.push(Token::String(string));
-            }
-            _ => {
-                let mut word = String::new();
-                while let Some(&ch) = chars.peek() {
-                    if [' ', '{', '}', '(', ')', '"', '\t', '\n'].contains(&ch) {
-                        break;
-                    }
-                    word.push(chars.next().unwrap());
-                }
-                if !word.is_empty() {
-                    tokens.push(Token::Word(word));
-                }
-            }
+/// Recovers the StarMath source kept in the annotation of MathML written by LibreOffice or by
+/// this crate. MathML without such an annotation cannot be converted back.
+pub fn mathml_to_starmath(mathml: &str) -> Result<String> {
+    let mut reader = Reader::from_str(mathml);
+    loop {
+        match reader.read_event()? {
+            Event::Start(start) if odf::is_starmath_annotation(&start)? => {
+                return odf::read_text(&mut reader);
+            }
+            Event::Eof => anyhow::bail!("MathML has no StarMath annotation"),
+            _ => {}
         }
     }
+}
 
-    tokens
+/// Serializes the parsed formula as JSON, for debugging and external tooling.
+#[cfg(feature = "serde")]
+pub fn starmath_to_ast_json(starmath: &str) -> Result<String> {
+    starmath_to_ast_json_with_options(starmath, &Options::default())
 }
 
-fn decode_html_entities(input: &str) -> String {
-    input
-        .replace("&amp;", "&")
-        .replace("&quot;", "\"")
-        .replace("“", "\"")
-        .replace("”", "\"")
-        .replace("&lt;", "<")
-        .replace("&gt;", ">")
+#[cfg(feature = "serde")]
+pub fn starmath_to_ast_json_with_options(starmath: &str, options: &Options) -> Result<String> {
+    let root = parser::parse(starmath, options)?;
+    Ok(serde_json::to_string_pretty(&root)?)
 }
 
 fn encode_html_entities(input: &str) -> String {
@@ -131,477 +168,3 @@ fn encode_html_entities(input: &str) -> String {
         .replace("<", "&lt;")
         .replace(">", "&gt;")
 }
-
-struct Parser {
-    tokens: Vec<Token>,
-    pos: usize,
-}
-
-impl Parser {
-    fn new(tokens: Vec<Token>) -> Self {
-        Parser { tokens, pos: 0 }
-    }
-
-    fn peek(&self) -> Option<&Token> {
-        self.tokens.get(self.pos)
-    }
-
-    fn advance(&mut self) -> Option<&Token> {
-        let token = self.tokens.get(self.pos);
-        if token.is_some() {
-            self.pos += 1;
-        }
-        token
-    }
-
-    fn parse_expression(&mut self, writer: &mut Writer<Cursor<Vec<u8>>>) -> Result<()> {
-        while let Some(token) = self.peek() {
-            if matches!(token, Token::RBrace) {
-                return Ok(());
-            }
-
-            self.parse_element(writer)?;
-        }
-        Ok(())
-    }
-
-    fn parse_element(&mut self, writer: &mut Writer<Cursor<Vec<u8>>>) -> Result<()> {
-        let token = match self.peek() {
-            Some(t) => t.clone(),
-            None => return Ok(()),
-        };
-
-        match token {
-            Token::Word(ref word) => match word.as_str() {
-                "acute" => self.parse_accent(writer, "´")?,
-                "sqrt" => self.parse_sqrt(writer)?,
-                "sum" => self.parse_sum(writer)?,
-                "left" => self.parse_left_fence(writer)?,
-                "right" => {
-                    self.advance();
-                    // Skip the closing parenthesis
-                    self.advance();
-                }
-                "%" => {
-                    self.advance();
-                    writer.write_event(Event::Start(BytesStart::new("mtext")))?;
-                    writer.write_event(Event::Text(BytesText::new("%")))?;
-                    writer.write_event(Event::End(BytesEnd::new("mtext")))?;
-                }
-                "=" => {
-                    self.advance();
-                    let mut mo = BytesStart::new("mo");
-                    mo.push_attribute(("stretchy", "false"));
-                    writer.write_event(Event::Start(mo))?;
-                    writer.write_event(Event::Text(BytesText::new("=")))?;
-                    writer.write_event(Event::End(BytesEnd::new("mo")))?;
-                }
-                "±" | "+-" => {
-                    self.advance();
-                    writer.write_event(Event::Start(BytesStart::new("mi")))?;
-                    writer.write_event(Event::Text(BytesText::new(word)))?;
-                    writer.write_event(Event::End(BytesEnd::new("mi")))?;
-                }
-                "−" | "-" => {
-                    self.advance();
-                    writer.write_event(Event::Start(BytesStart::new("mo")))?;
-                    writer.write_event(Event::Text(BytesText::new("−")))?;
-                    writer.write_event(Event::End(BytesEnd::new("mo")))?;
-                }
-                "×" | "*" | "times" => {
-                    self.advance();
-                    writer.write_event(Event::Start(BytesStart::new("mo")))?;
-                    writer.write_event(Event::Text(BytesText::new("×")))?;
-                    writer.write_event(Event::End(BytesEnd::new("mo")))?;
-                }
-                _ => {
-                    self.advance();
-
-                    // Check if rsub, ^, or over follows this token
-                    if let Some(Token::Word(op)) = self.peek() {
-                        if op == "rsub" {
-                            writer.write_event(Event::Start(BytesStart::new("msub")))?;
-
-                            // Write the base
-                            self.write_token_content(writer, word)?;
-
-                            self.advance(); // skip "rsub"
-
-                            // Parse the subscript
-                            self.parse_element(writer)?;
-
-                            writer.write_event(Event::End(BytesEnd::new("msub")))?;
-                            return Ok(());
-                        } else if op == "^" {
-                            writer.write_event(Event::Start(BytesStart::new("msup")))?;
-
-                            // Write the base
-                            self.write_token_content(writer, word)?;
-
-                            self.advance(); // skip "^"
-
-                            // Parse the exponent
-                            self.parse_element(writer)?;
-
-                            writer.write_event(Event::End(BytesEnd::new("msup")))?;
-                            return Ok(());
-                        } else if op == "over" {
-                            writer.write_event(Event::Start(BytesStart::new("mfrac")))?;
-
-                            // Write the numerator
-                            self.write_token_content(writer, word)?;
-
-                            self.advance(); // skip "over"
-
-                            // Parse the denominator
-                            self.parse_element(writer)?;
-
-                            writer.write_event(Event::End(BytesEnd::new("mfrac")))?;
-                            return Ok(());
-                        }
-                    }
-
-                    // Normal identifier or number
-                    self.write_token_content(writer, word)?;
-                }
-            },
-            Token::String(ref s) => {
-                self.advance();
-                writer.write_event(Event::Start(BytesStart::new("mtext")))?;
-                writer.write_event(Event::Text(BytesText::new(s)))?;
-                writer.write_event(Event::End(BytesEnd::new("mtext")))?;
-            }
-            Token::LBrace => {
-                self.advance();
-                self.parse_group(writer)?;
-            }
-            Token::RBrace => {
-                return Ok(());
-            }
-            Token::LParen | Token::RParen => {
-                // Parentheses are handled by parse_left_fence
-                // Skip them if encountered here
-                self.advance();
-            }
-        }
-        Ok(())
-    }
-
-    fn parse_group(&mut self, writer: &mut Writer<Cursor<Vec<u8>>>) -> Result<()> {
-        // Look ahead to see what follows this group
-        let group_start = self.pos;
-        let mut brace_count = 1;
-        let mut temp_pos = self.pos;
-
-        while temp_pos < self.tokens.len() && brace_count > 0 {
-            match &self.tokens[temp_pos] {
-                Token::LBrace => brace_count += 1,
-                Token::RBrace => brace_count -= 1,
-                _ => {}
-            }
-            temp_pos += 1;
-        }
-
-        let group_end = temp_pos;
-
-        // First, check if there's an "over" operator INSIDE this group (at brace level 0)
-        let mut over_pos = None;
-        let mut inner_brace_count = 0;
-        for i in group_start..group_end - 1 {
-            match &self.tokens[i] {
-                Token::LBrace => inner_brace_count += 1,
-                Token::RBrace => inner_brace_count -= 1,
-                Token::Word(w) if w == "over" && inner_brace_count == 0 => {
-                    over_pos = Some(i);
-                    break;
-                }
-                _ => {}
-            }
-        }
-
-        // If there's an "over" inside the group, create a fraction
-        if let Some(over_idx) = over_pos {
-            writer.write_event(Event::Start(BytesStart::new("mfrac")))?;
-
-            // Parse numerator (tokens before "over")
-            let mut num_parser = Parser {
-                tokens: self.tokens[group_start..over_idx].to_vec(),
-                pos: 0,
-            };
-            num_parser.parse_expression(writer)?;
-
-            // Parse denominator (tokens after "over")
-            let mut denom_parser = Parser {
-                tokens: self.tokens[over_idx + 1..group_end - 1].to_vec(),
-                pos: 0,
-            };
-            denom_parser.parse_expression(writer)?;
-
-            writer.write_event(Event::End(BytesEnd::new("mfrac")))?;
-
-            self.pos = group_end;
-            return Ok(());
-        }
-
-        // Check what follows
-        if let Some(Token::Word(op)) = self.tokens.get(group_end) {
-            match op.as_str() {
-                "rsub" => {
-                    writer.write_event(Event::Start(BytesStart::new("msub")))?;
-
-                    let mut sub_parser = Parser {
-                        tokens: self.tokens[group_start..group_end - 1].to_vec(),
-                        pos: 0,
-                    };
-                    sub_parser.parse_expression(writer)?;
-
-                    self.pos = group_end + 1; // Skip past rsub
-                    self.parse_element(writer)?;
-
-                    writer.write_event(Event::End(BytesEnd::new("msub")))?;
-
-                    // Skip the closing brace of the subscript if it exists
-                    if matches!(self.peek(), Some(Token::RBrace)) {
-                        self.advance();
-                    }
-
-                    return Ok(());
-                }
-                "^" => {
-                    writer.write_event(Event::Start(BytesStart::new("msup")))?;
-
-                    let mut sub_parser = Parser {
-                        tokens: self.tokens[group_start..group_end - 1].to_vec(),
-                        pos: 0,
-                    };
-                    sub_parser.parse_expression(writer)?;
-
-                    self.pos = group_end + 1; // Skip past ^
-                    self.parse_element(writer)?;
-
-                    writer.write_event(Event::End(BytesEnd::new("msup")))?;
-
-                    // Skip the closing brace of the superscript if it exists
-                    if matches!(self.peek(), Some(Token::RBrace)) {
-                        self.advance();
-                    }
-
-                    return Ok(());
-                }
-                "over" => {
-                    writer.write_event(Event::Start(BytesStart::new("mfrac")))?;
-
-                    let mut sub_parser = Parser {
-                        tokens: self.tokens[group_start..group_end - 1].to_vec(),
-                        pos: 0,
-                    };
-                    sub_parser.parse_expression(writer)?;
-
-                    self.pos = group_end + 1; // Skip past over
-                    self.parse_element(writer)?;
-
-                    writer.write_event(Event::End(BytesEnd::new("mfrac")))?;
-
-                    // Skip the closing brace of the denominator if it exists
-                    if matches!(self.peek(), Some(Token::RBrace)) {
-                        self.advance();
-                    }
-
-                    return Ok(());
-                }
-                _ => {}
-            }
-        }
-
-        // Regular group - parse its contents
-        let mut sub_parser = Parser {
-            tokens: self.tokens[group_start..group_end - 1].to_vec(),
-            pos: 0,
-        };
-        sub_parser.parse_expression(writer)?;
-
-        self.pos = group_end;
-
-        Ok(())
-    }
-
-    fn parse_accent(&mut self, writer: &mut Writer<Cursor<Vec<u8>>>, accent: &str) -> Result<()> {
-        self.advance(); // skip "acute"
-
-        let mut attr = BytesStart::new("mover");
-        attr.push_attribute(("accent", "true"));
-        writer.write_event(Event::Start(attr))?;
-
-        self.parse_element(writer)?;
-
-        let mut mo = BytesStart::new("mo");
-        mo.push_attribute(("stretchy", "false"));
-        writer.write_event(Event::Start(mo))?;
-        writer.write_event(Event::Text(BytesText::new(accent)))?;
-        writer.write_event(Event::End(BytesEnd::new("mo")))?;
-
-        writer.write_event(Event::End(BytesEnd::new("mover")))?;
-
-        Ok(())
-    }
-
-    fn parse_sqrt(&mut self, writer: &mut Writer<Cursor<Vec<u8>>>) -> Result<()> {
-        self.advance(); // skip "sqrt"
-
-        writer.write_event(Event::Start(BytesStart::new("msqrt")))?;
-
-        self.parse_element(writer)?;
-
-        writer.write_event(Event::End(BytesEnd::new("msqrt")))?;
-
-        Ok(())
-    }
-
-    fn parse_sum(&mut self, writer: &mut Writer<Cursor<Vec<u8>>>) -> Result<()> {
-        self.advance(); // skip "sum"
-
-        let mut mo = BytesStart::new("mo");
-        mo.push_attribute(("stretchy", "false"));
-        writer.write_event(Event::Start(mo))?;
-        writer.write_event(Event::Text(BytesText::new("∑")))?;
-        writer.write_event(Event::End(BytesEnd::new("mo")))?;
-
-        self.parse_element(writer)?;
-
-        Ok(())
-    }
-
-    fn parse_left_fence(&mut self, writer: &mut Writer<Cursor<Vec<u8>>>) -> Result<()> {
-        self.advance(); // skip "left"
-
-        // Get the opening fence
-        let fence = match self.peek() {
-            Some(Token::Word(f)) => {
-                let s = f.clone();
-                self.advance();
-                s
-            }
-            Some(Token::LParen) => {
-                self.advance();
-                "(".to_string()
-            }
-            _ => return Ok(()),
-        };
-
-        writer.write_event(Event::Start(BytesStart::new("mrow")))?;
-
-        // Opening fence
-        let mut mo = BytesStart::new("mo");
-        mo.push_attribute(("fence", "true"));
-        mo.push_attribute(("form", "prefix"));
-        mo.push_attribute(("stretchy", "true"));
-        writer.write_event(Event::Start(mo))?;
-        writer.write_event(Event::Text(BytesText::new(&fence)))?;
-        writer.write_event(Event::End(BytesEnd::new("mo")))?;
-
-        // Content inside fence
-        writer.write_event(Event::Start(BytesStart::new("mrow")))?;
-
-        // Parse until we hit "right"
-        while let Some(token) = self.peek() {
-            if let Token::Word(w) = token
-                && w == "right"
-            {
-                break;
-            }
-            self.parse_element(writer)?;
-        }
-
-        writer.write_event(Event::End(BytesEnd::new("mrow")))?;
-
-        // Closing fence
-        self.advance(); // skip "right"
-        let closing_fence = match self.peek() {
-            Some(Token::Word(f)) => {
-                let s = f.clone();
-                self.advance();
-                s
-            }
-            Some(Token::RParen) => {
-                self.advance();
-                ")".to_string()
-            }
-            _ => return Ok(()),
-        };
-
-        let mut mo = BytesStart::new("mo");
-        mo.push_attribute(("fence", "true"));
-        mo.push_attribute(("form", "postfix"));
-        mo.push_attribute(("stretchy", "true"));
-        writer.write_event(Event::Start(mo))?;
-        writer.write_event(Event::Text(BytesText::new(&closing_fence)))?;
-        writer.write_event(Event::End(BytesEnd::new("mo")))?;
-
-        writer.write_event(Event::End(BytesEnd::new("mrow")))?;
-
-        Ok(())
-    }
-
-    fn write_token_content(&self, writer: &mut Writer<Cursor<Vec<u8>>>, word: &str) -> Result<()> {
-        // Determine if this is a number or identifier
-        let is_number = word
-            .chars()
-            .all(|c| c.is_ascii_digit() || c == ',' || c == '.');
-        if is_number {
-            writer.write_event(Event::Start(BytesStart::new("mn")))?;
-            writer.write_event(Event::Text(BytesText::new(word)))?;
-            writer.write_event(Event::End(BytesEnd::new("mn")))?;
-        } else {
-            // Check if this is a standard mathematical function (should be upright)
-            let is_function = matches!(
-                word,
-                "sin"
-                    | "cos"
-                    | "tan"
-                    | "sec"
-                    | "csc"
-                    | "cot"
-                    | "sinh"
-                    | "cosh"
-                    | "tanh"
-                    | "sech"
-                    | "csch"
-                    | "coth"
-                    | "arcsin"
-                    | "arccos"
-                    | "arctan"
-                    | "arcsec"
-                    | "arccsc"
-                    | "arccot"
-                    | "log"
-                    | "ln"
-                    | "lg"
-                    | "exp"
-                    | "lim"
-                    | "sup"
-                    | "inf"
-                    | "max"
-                    | "min"
-                    | "det"
-                    | "dim"
-                    | "ker"
-                    | "deg"
-                    | "gcd"
-                    | "lcm"
-                    | "Pr"
-                    | "hom"
-                    | "arg"
-                    | "mod"
-            );
-
-            let mut mi = BytesStart::new("mi");
-            if word.len() > 1 && !is_function {
-                mi.push_attribute(("mathvariant", "italic"));
-            }
-            writer.write_event(Event::Start(mi))?;
-            writer.write_event(Event::Text(BytesText::new(word)))?;
-            writer.write_event(Event::End(BytesEnd::new("mi")))?;
-        }
-        Ok(())
-    }
-}
@@ -0,0 +1,386 @@
+use anyhow::Result;
+
+use crate::alphanumeric::{self, Variant};
+use crate::ast::{Font, Node, precedence};
+use crate::symbols::greek_name;
+use crate::{INVISIBLE_TIMES, Options, parser};
+
+const SUPERSCRIPTS: &[(char, char)] = &[
+    ('0', '⁰'),
+    ('1', '¹'),
+    ('2', '²'),
+    ('3', '³'),
+    ('4', '⁴'),
+    ('5', '⁵'),
+    ('6', '⁶'),
+    ('7', '⁷'),
+    ('8', '⁸'),
+    ('9', '⁹'),
+    ('+', '⁺'),
+    ('-', '⁻'),
+    ('−', '⁻'),
+    ('=', '⁼'),
+    ('(', '⁽'),
+    (')', '⁾'),
+    ('a', 'ᵃ'),
+    ('b', 'ᵇ'),
+    ('c', 'ᶜ'),
+    ('d', 'ᵈ'),
+    ('e', 'ᵉ'),
+    ('f', 'ᶠ'),
+    ('g', 'ᵍ'),
+    ('h', 'ʰ'),
+    ('i', 'ⁱ'),
+    ('j', 'ʲ'),
+    ('k', 'ᵏ'),
+    ('l', 'ˡ'),
+    ('m', 'ᵐ'),
+    ('n', 'ⁿ'),
+    ('o', 'ᵒ'),
+    ('p', 'ᵖ'),
+    ('r', 'ʳ'),
+    ('s', 'ˢ'),
+    ('t', 'ᵗ'),
+    ('u', 'ᵘ'),
+    ('v', 'ᵛ'),
+    ('w', 'ʷ'),
+    ('x', 'ˣ'),
+    ('y', 'ʸ'),
+    ('z', 'ᶻ'),
+];
+
+const SUBSCRIPTS: &[(char, char)] = &[
+    ('0', '₀'),
+    ('1', '₁'),
+    ('2', '₂'),
+    ('3', '₃'),
+    ('4', '₄'),
+    ('5', '₅'),
+    ('6', '₆'),
+    ('7', '₇'),
+    ('8', '₈'),
+    ('9', '₉'),
+    ('+', '₊'),
+    ('-', '₋'),
+    ('−', '₋'),
+    ('=', '₌'),
+    ('(', '₍'),
+    (')', '₎'),
+    ('a', 'ₐ'),
+    ('e', 'ₑ'),
+    ('h', 'ₕ'),
+    ('i', 'ᵢ'),
+    ('j', 'ⱼ'),
+    ('k', 'ₖ'),
+    ('l', 'ₗ'),
+    ('m', 'ₘ'),
+    ('n', 'ₙ'),
+    ('o', 'ₒ'),
+    ('p', 'ₚ'),
+    ('r', 'ᵣ'),
+    ('s', 'ₛ'),
+    ('t', 'ₜ'),
+    ('u', 'ᵤ'),
+    ('v', 'ᵥ'),
+    ('x', 'ₓ'),
+];
+
+/// Renders StarMath as a single line of plain Unicode, like `√(x²+1)/2`.
+pub fn starmath_to_unicode(starmath: &str) -> Result<String> {
+    starmath_to_unicode_with_options(starmath, &Options::default())
+}
+
+pub fn starmath_to_unicode_with_options(starmath: &str, options: &Options) -> Result<String> {
+    let root = parser::parse(starmath, options)?;
+    Ok(Linear::Unicode.render(&root, Font::default()))
+}
+
+/// Renders StarMath as AsciiMath, like `sqrt(x^2+1)/2`.
+pub fn starmath_to_asciimath(starmath: &str) -> Result<String> {
+    starmath_to_asciimath_with_options(starmath, &Options::default())
+}
+
+pub fn starmath_to_asciimath_with_options(starmath: &str, options: &Options) -> Result<String> {
+    let root = parser::parse(starmath, options)?;
+    Ok(Linear::AsciiMath.render(&root, Font::default()))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Linear {
+    Unicode,
+    AsciiMath,
+}
+
+impl Linear {
+    fn render(self, node: &Node, font: Font) -> String {
+        match node {
+            Node::Number(number) => self.token(number, font),
+            Node::Identifier(name) if precedence(name).is_some() => self.operator(name),
+            Node::Identifier(name) | Node::Function(name) => self.token(name, font),
+            Node::Operator { symbol, .. } => self.operator(symbol),
+            Node::Text(text) => match self {
+                Linear::Unicode => text.clone(),
+                Linear::AsciiMath => format!("\"{}\"", text),
+            },
+            Node::Row(children) => self.render_row(children, font),
+            Node::Sub(base, sub) => {
+                format!(
+                    "{}{}",
+                    self.operand(base, font),
+                    self.script(sub, font, false)
+                )
+            }
+            Node::Sup(base, sup) => {
+                format!(
+                    "{}{}",
+                    self.operand(base, font),
+                    self.script(sup, font, true)
+                )
+            }
+            Node::Frac(num, den) => {
+                let num = self.fraction_part(num, font);
+                let den = self.fraction_part(den, font);
+                format!("{}/{}", num, den)
+            }
+            Node::Sqrt(body) => {
+                let body = self.grouped(body, font);
+                match self {
+                    Linear::Unicode => format!("√{}", body),
+                    Linear::AsciiMath => format!("sqrt{}", body),
+                }
+            }
+            Node::Root(index, body) => {
+                let radicand = self.grouped(body, font);
+                match (self, self.render(index, font).as_str()) {
+                    (Linear::Unicode, "3") => format!("∛{}", radicand),
+                    (Linear::Unicode, "4") => format!("∜{}", radicand),
+                    (Linear::Unicode, _) => {
+                        format!("{}√{}", self.script(index, font, true), radicand)
+                    }
+                    (Linear::AsciiMath, index) => {
+                        format!("root({})({})", index, self.render(body, font))
+                    }
+                }
+            }
+            Node::Accent(base, accent) => {
+                let base = self.render(base, font);
+                match (self, accent.as_str()) {
+                    // A combining accent only works on a single character
+                    (Linear::Unicode, "´") if base.chars().count() == 1 => {
+                        format!("{}\u{301}", base)
+                    }
+                    (Linear::Unicode, _) => format!("{}({})", accent, base),
+                    (Linear::AsciiMath, _) => format!("overset({})({})", accent, base),
+                }
+            }
+            Node::Fenced { open, body, close } => {
+                let close = close.as_deref().unwrap_or("");
+                match self {
+                    Linear::Unicode => format!("{}{}{}", open, self.render(body, font), close),
+                    // "left(" and "right)" keep mismatched fences valid AsciiMath
+                    Linear::AsciiMath => {
+                        format!("{} {} {}", open, self.render(body, font), close)
+                    }
+                }
+            }
+            Node::Styled(style, body) => self.render(body, font.with(*style)),
+            Node::LargeOp {
+                symbol,
+                from,
+                to,
+                body,
+            } => {
+                let mut result = self.operator(symbol);
+                if let Some(from) = from {
+                    result.push_str(&self.script(from, font, false));
+                }
+                if let Some(to) = to {
+                    result.push_str(&self.script(to, font, true));
+                }
+                if let Some(body) = body {
+                    let rendered = self.render(body, font);
+                    result.push(' ');
+                    if is_factor(body) {
+                        result.push_str(&rendered);
+                    } else {
+                        result.push_str(&format!("({})", rendered));
+                    }
+                }
+                result
+            }
+            Node::Matrix(rows) => {
+                let rows: Vec<_> = rows
+                    .iter()
+                    .map(|row| {
+                        let cells: Vec<_> =
+                            row.iter().map(|cell| self.render(cell, font)).collect();
+                        match self {
+                            Linear::Unicode => cells.join(" "),
+                            Linear::AsciiMath => format!("({})", cells.join(",")),
+                        }
+                    })
+                    .collect();
+                match self {
+                    Linear::Unicode => format!("[{}]", rows.join("; ")),
+                    Linear::AsciiMath => format!("[{}]", rows.join(",")),
+                }
+            }
+        }
+    }
+
+    fn render_row(self, children: &[Node], font: Font) -> String {
+        let mut result = String::new();
+        for child in children {
+            let rendered = self.render(child, font);
+            if rendered.is_empty() {
+                continue;
+            }
+            let relation = is_relation(child);
+            let separate = match self {
+                Linear::AsciiMath => !result.is_empty(),
+                // Keep words apart and give relations some room
+                Linear::Unicode => {
+                    relation
+                        || result.ends_with(|c: char| c.is_alphabetic())
+                            && rendered.starts_with(|c: char| c.is_alphanumeric())
+                }
+            };
+            if separate && !result.is_empty() && !result.ends_with(' ') {
+                result.push(' ');
+            }
+            result.push_str(&rendered);
+            if relation && self == Linear::Unicode {
+                result.push(' ');
+            }
+        }
+        result.trim_end().to_string()
+    }
+
+    fn token(self, text: &str, font: Font) -> String {
+        match self {
+            Linear::Unicode => {
+                // Plain text already reads as math italic, only explicit styles are kept
+                let variant = Variant::new(font.bold == Some(true), font.italic == Some(true));
+                alphanumeric::styled(text, variant)
+            }
+            Linear::AsciiMath => {
+                let text = match greek_name(text) {
+                    Some(name) => name.to_string(),
+                    None => text.to_string(),
+                };
+                if font.bold == Some(true) {
+                    format!("bb({})", text)
+                } else {
+                    text
+                }
+            }
+        }
+    }
+
+    fn operator(self, symbol: &str) -> String {
+        if symbol == INVISIBLE_TIMES {
+            return String::new();
+        }
+        if self == Linear::Unicode {
+            return symbol.to_string();
+        }
+        let name = match symbol {
+            "×" => "xx",
+            "·" => "*",
+            "÷" => "-:",
+            "−" => "-",
+            "±" | "+-" => "+-",
+            "≤" => "<=",
+            "≥" => ">=",
+            "≠" | "<>" => "!=",
+            "∑" => "sum",
+            "∏" => "prod",
+            "∐" => "coprod",
+            "∫" => "int",
+            "∬" => "iint",
+            "∭" => "iiint",
+            "∮" => "oint",
+            _ => symbol,
+        };
+        name.to_string()
+    }
+
+    fn operand(self, node: &Node, font: Font) -> String {
+        let rendered = self.render(node, font);
+        if matches!(node, Node::Row(children) if children.len() > 1) {
+            format!("({})", rendered)
+        } else {
+            rendered
+        }
+    }
+
+    fn grouped(self, node: &Node, font: Font) -> String {
+        let rendered = self.render(node, font);
+        if node.is_atom() {
+            rendered
+        } else {
+            format!("({})", rendered)
+        }
+    }
+
+    fn script(self, node: &Node, font: Font, superscript: bool) -> String {
+        let rendered = self.render(node, font);
+        if self == Linear::Unicode {
+            let table = if superscript {
+                SUPERSCRIPTS
+            } else {
+                SUBSCRIPTS
+            };
+            // Spacing around relations gets lost in scripts
+            let mapped: Option<String> = rendered
+                .chars()
+                .filter(|c| *c != ' ')
+                .map(|c| table.iter().find(|(plain, _)| *plain == c).map(|(_, s)| *s))
+                .collect();
+            if let Some(mapped) = mapped
+                && !mapped.is_empty()
+            {
+                return mapped;
+            }
+        }
+        let marker = if superscript { '^' } else { '_' };
+        if node.is_atom() {
+            format!("{}{}", marker, rendered)
+        } else {
+            format!("{}({})", marker, rendered)
+        }
+    }
+
+    // Anything but a single factor is parenthesized, so a/(b+c) and (a b)/c read unambiguously
+    fn fraction_part(self, node: &Node, font: Font) -> String {
+        let rendered = self.render(node, font);
+        if is_factor(node) {
+            rendered
+        } else {
+            format!("({})", rendered)
+        }
+    }
+}
+
+fn node_precedence(node: &Node) -> Option<u8> {
+    match node {
+        Node::Operator { precedence, .. } => Some(*precedence),
+        // Symbols without a keyword reach the tree as identifiers
+        Node::Identifier(name) => precedence(name),
+        _ => None,
+    }
+}
+
+// Atoms, along with scripts and roots, which hold together next to an operator
+fn is_factor(node: &Node) -> bool {
+    match node {
+        Node::Sub(..) | Node::Sup(..) | Node::Sqrt(_) | Node::Root(..) | Node::Accent(..) => true,
+        Node::Row(children) => matches!(children.as_slice(), [child] if is_factor(child)),
+        Node::Styled(_, body) => is_factor(body),
+        _ => node.is_atom(),
+    }
+}
+
+fn is_relation(node: &Node) -> bool {
+    node_precedence(node) == Some(1)
+}
@@ -0,0 +1,520 @@
+use std::fmt;
+use std::ops::Range;
+
+use crate::format::{canonical_keyword, is_plain};
+use crate::parser::{Token, tokenize_spanned};
+use crate::symbols::greek_letter;
+
+// LibreOffice keywords this converter doesn't know, which end up as plain identifiers
+const UNSUPPORTED: &[&str] = &[
+    "cdot",
+    "neq",
+    "leslant",
+    "geslant",
+    "infinity",
+    "partial",
+    "nabla",
+    "in",
+    "notin",
+    "subset",
+    "union",
+    "intersection",
+    "forall",
+    "exists",
+    "dotsaxis",
+    "dotslow",
+    "hat",
+    "bar",
+    "vec",
+    "tilde",
+    "dot",
+    "ddot",
+    "overline",
+    "underline",
+    "grave",
+    "breve",
+    "check",
+    "circle",
+    "abs",
+    "fact",
+    "binom",
+    "stack",
+    "color",
+    "size",
+    "font",
+    "func",
+    "newline",
+    "lsub",
+    "lsup",
+    "csub",
+    "csup",
+    "rsup",
+    "underbrace",
+    "overbrace",
+    "frac",
+];
+
+// Spellings accepted for compatibility, with the one LibreOffice writes itself
+const DEPRECATED: &[(&str, &str)] = &[("italic", "ital")];
+
+// Operators that can be typed both as a Unicode character and as a keyword
+const OPERATOR_SPELLINGS: &[(&str, &str)] = &[
+    ("×", "times"),
+    ("±", "+-"),
+    ("−", "-"),
+    ("≤", "<="),
+    ("≥", ">="),
+    ("≠", "<>"),
+];
+
+// Operators after which a numerator given to "over" stops
+const BINARY_OPERATORS: &[&str] = &[
+    "+", "-", "−", "+-", "±", "-+", "∓", "=", "<", ">", "<=", ">=", "<>", "≤", "≥", "≠", "times",
+    "×", "*",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// Replacement of a byte range of the source that resolves a diagnostic.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fix {
+    pub span: Range<usize>,
+    pub replacement: String,
+}
+
+/// A questionable construct found in StarMath source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// Stable identifier of the check, like `redundant-braces`.
+    pub code: &'static str,
+    pub severity: Severity,
+    /// Byte range of the offending source.
+    pub span: Range<usize>,
+    pub message: String,
+    pub fix: Option<Fix>,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}[{}] at {}..{}: {}",
+            self.severity, self.code, self.span.start, self.span.end, self.message
+        )
+    }
+}
+
+/// Reports questionable StarMath, ordered by position in the source.
+pub fn lint(starmath: &str) -> Vec<Diagnostic> {
+    let tokens = tokenize_spanned(starmath);
+    let mut linter = Linter {
+        source: starmath,
+        tokens: &tokens,
+        diagnostics: Vec::new(),
+    };
+    linter.check_fences();
+    let groups = linter.check_braces();
+    for group in &groups {
+        linter.check_redundant_braces(group);
+    }
+    linter.check_levels(&groups);
+    linter.check_words();
+    linter.check_operator_spellings();
+
+    let mut diagnostics = linter.diagnostics;
+    diagnostics.sort_by_key(|diagnostic| (diagnostic.span.start, diagnostic.span.end));
+    diagnostics
+}
+
+/// Applies the fixes of the diagnostics, skipping any that overlap an earlier one.
+pub fn apply_fixes(starmath: &str, diagnostics: &[Diagnostic]) -> String {
+    let mut fixes: Vec<_> = diagnostics.iter().filter_map(|d| d.fix.as_ref()).collect();
+    fixes.sort_by_key(|fix| fix.span.start);
+
+    let mut fixed = String::new();
+    let mut pos = 0;
+    for fix in fixes {
+        if fix.span.start < pos {
+            continue;
+        }
+        fixed.push_str(&starmath[pos..fix.span.start]);
+        fixed.push_str(&fix.replacement);
+        pos = fix.span.end;
+    }
+    fixed.push_str(&starmath[pos..]);
+    fixed
+}
+
+// Token indices of a matched pair of braces
+struct Group {
+    open: usize,
+    close: usize,
+}
+
+struct Linter<'a> {
+    source: &'a str,
+    tokens: &'a [(Token, Range<usize>)],
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Linter<'a> {
+    fn report(
+        &mut self,
+        code: &'static str,
+        severity: Severity,
+        span: Range<usize>,
+        message: String,
+    ) {
+        self.diagnostics.push(Diagnostic {
+            code,
+            severity,
+            span,
+            message,
+            fix: None,
+        });
+    }
+
+    fn report_with_fix(
+        &mut self,
+        code: &'static str,
+        span: Range<usize>,
+        message: String,
+        fix: Fix,
+    ) {
+        self.diagnostics.push(Diagnostic {
+            code,
+            severity: Severity::Warning,
+            span,
+            message,
+            fix: Some(fix),
+        });
+    }
+
+    fn word(&self, index: usize) -> Option<&'a str> {
+        match self.tokens.get(index) {
+            Some((Token::Word(word), _)) => Some(word),
+            _ => None,
+        }
+    }
+
+    fn span(&self, index: usize) -> Range<usize> {
+        self.tokens[index].1.clone()
+    }
+
+    fn check_fences(&mut self) {
+        let mut open = Vec::new();
+        for index in 0..self.tokens.len() {
+            match self.word(index) {
+                Some("left") => open.push(index),
+                Some("right") if open.pop().is_none() => {
+                    self.report(
+                        "unbalanced-fence",
+                        Severity::Error,
+                        self.span(index),
+                        "right without a matching left".to_string(),
+                    );
+                }
+                _ => {}
+            }
+        }
+        for index in open {
+            self.report(
+                "unbalanced-fence",
+                Severity::Error,
+                self.span(index),
+                "left without a matching right".to_string(),
+            );
+        }
+    }
+
+    fn check_braces(&mut self) -> Vec<Group> {
+        let mut groups = Vec::new();
+        let mut open = Vec::new();
+        for (index, (token, span)) in self.tokens.iter().enumerate() {
+            match token {
+                Token::LBrace => open.push(index),
+                Token::RBrace => match open.pop() {
+                    Some(start) => groups.push(Group {
+                        open: start,
+                        close: index,
+                    }),
+                    None => self.report(
+                        "unbalanced-brace",
+                        Severity::Error,
+                        span.clone(),
+                        "closing brace without a matching opening brace".to_string(),
+                    ),
+                },
+                _ => {}
+            }
+        }
+        for index in open {
+            self.report(
+                "unbalanced-brace",
+                Severity::Error,
+                self.span(index),
+                "opening brace is never closed".to_string(),
+            );
+        }
+        groups.sort_by_key(|group| group.open);
+        groups
+    }
+
+    fn check_redundant_braces(&mut self, group: &Group) {
+        // The braces after matrix belong to its syntax, after left or right they are the fence
+        if group.open > 0 && matches!(self.word(group.open - 1), Some("matrix" | "left" | "right"))
+        {
+            return;
+        }
+        let inner = group.open + 1..group.close;
+        let redundant = match inner.len() {
+            1 => is_plain(&self.tokens[inner.start].0),
+            // Braces directly around another group
+            len if len >= 2 => {
+                matches!(self.tokens[inner.start].0, Token::LBrace)
+                    && self.matching_close(inner.start) == Some(inner.end - 1)
+            }
+            _ => false,
+        };
+        if !redundant {
+            return;
+        }
+
+        let span = self.span(group.open).start..self.span(group.close).end;
+        let content = &self.source[self.span(inner.start).start..self.span(inner.end - 1).end];
+        // Keeps the content from merging with neighbouring words
+        let before = self.source[..span.start].chars().next_back();
+        let after = self.source[span.end..].chars().next();
+        let separate =
+            |ch: Option<char>| ch.is_some_and(|c| !c.is_whitespace() && c != '{' && c != '}');
+        let mut replacement = String::new();
+        if separate(before) {
+            replacement.push(' ');
+        }
+        replacement.push_str(content);
+        if separate(after) {
+            replacement.push(' ');
+        }
+        self.report_with_fix(
+            "redundant-braces",
+            span.clone(),
+            format!("braces around `{}` group nothing", content),
+            Fix { span, replacement },
+        );
+    }
+
+    fn matching_close(&self, open: usize) -> Option<usize> {
+        let mut depth = 0;
+        for (index, (token, _)) in self.tokens.iter().enumerate().skip(open) {
+            match token {
+                Token::LBrace => depth += 1,
+                Token::RBrace => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some(index);
+                    }
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+
+    // Checks "over" within each brace level, the whole input being the outermost one
+    fn check_levels(&mut self, groups: &[Group]) {
+        self.check_level(0..self.tokens.len());
+        for group in groups {
+            self.check_level(group.open + 1..group.close);
+        }
+    }
+
+    fn check_level(&mut self, range: Range<usize>) {
+        // Items of the level, a nested group counting as one
+        let mut items = Vec::new();
+        let mut index = range.start;
+        while index < range.end {
+            let start = index;
+            if matches!(self.tokens[index].0, Token::LBrace) {
+                index = self.matching_close(index).unwrap_or(range.end - 1);
+            }
+            items.push(start..index + 1);
+            index += 1;
+        }
+
+        let overs: Vec<_> = items
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| item.len() == 1 && self.word(item.start) == Some("over"))
+            .map(|(position, _)| position)
+            .collect();
+        if let Some(&second) = overs.get(1) {
+            self.report(
+                "ambiguous-over",
+                Severity::Warning,
+                self.span(items[second].start),
+                "chained over is ambiguous, use braces to show which fraction is nested"
+                    .to_string(),
+            );
+        }
+
+        for &position in &overs {
+            // "a + b over c" divides b alone
+            if position >= 2
+                && let Some(operator) = self.word(items[position - 2].start)
+                && items[position - 2].len() == 1
+                && BINARY_OPERATORS.contains(&operator)
+            {
+                let numerator = &items[position - 1];
+                let numerator = &self.source
+                    [self.span(numerator.start).start..self.span(numerator.end - 1).end];
+                self.report(
+                    "over-precedence",
+                    Severity::Warning,
+                    self.span(items[position].start),
+                    format!(
+                        "over only takes `{}` as numerator, use braces to divide more",
+                        numerator
+                    ),
+                );
+            }
+        }
+    }
+
+    fn check_words(&mut self) {
+        for index in 0..self.tokens.len() {
+            let Some(word) = self.word(index) else {
+                continue;
+            };
+            let span = self.span(index);
+            let fix = |replacement: &str| Fix {
+                span: span.clone(),
+                replacement: replacement.to_string(),
+            };
+
+            if let Some(&(_, preferred)) = DEPRECATED.iter().find(|(old, _)| *old == word) {
+                let message = format!("`{}` is deprecated, write `{}`", word, preferred);
+                let fix = fix(preferred);
+                self.report_with_fix("deprecated-keyword", span, message, fix);
+            } else if let Some(keyword) = canonical_keyword(word)
+                && keyword != word
+            {
+                let message = format!(
+                    "`{}` is written as an identifier, keywords are lowercase: `{}`",
+                    word, keyword
+                );
+                let fix = fix(keyword);
+                self.report_with_fix("keyword-case", span, message, fix);
+            } else if greek_letter(word).is_some() {
+                // Not fixed automatically, a variable may well be named pi
+                let message = format!(
+                    "`{}` is written as an identifier, did you mean `%{}`?",
+                    word, word
+                );
+                self.report("greek-without-percent", Severity::Warning, span, message);
+            } else if UNSUPPORTED.contains(&word) {
+                self.report(
+                    "unknown-command",
+                    Severity::Warning,
+                    span,
+                    format!(
+                        "`{}` is not supported and is written as an identifier",
+                        word
+                    ),
+                );
+            } else if let Some(name) = word.strip_prefix('%')
+                && !name.is_empty()
+                && greek_letter(name).is_none()
+            {
+                self.report(
+                    "unknown-command",
+                    Severity::Warning,
+                    span,
+                    format!("unknown symbol `{}` is written as an identifier", word),
+                );
+            }
+        }
+    }
+
+    // Flags Unicode operators in formulas that otherwise spell operators as keywords
+    fn check_operator_spellings(&mut self) {
+        let words: Vec<_> = (0..self.tokens.len())
+            .filter_map(|index| self.word(index).map(|word| (index, word)))
+            .collect();
+        let uses_keywords = words.iter().any(|(_, word)| {
+            OPERATOR_SPELLINGS
+                .iter()
+                .any(|(_, keyword)| keyword == word)
+        });
+        if !uses_keywords {
+            return;
+        }
+        for (index, word) in words {
+            if let Some((symbol, keyword)) = OPERATOR_SPELLINGS
+                .iter()
+                .find(|(symbol, _)| *symbol == word)
+            {
+                let span = self.span(index);
+                let fix = Fix {
+                    span: span.clone(),
+                    replacement: keyword.to_string(),
+                };
+                self.report_with_fix(
+                    "mixed-operators",
+                    span,
+                    format!(
+                        "`{}` mixed with keyword operators, write `{}`",
+                        symbol, keyword
+                    ),
+                    fix,
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn asterisk_is_not_deprecated() {
+        assert!(lint("a * b").is_empty());
+    }
+
+    #[test]
+    fn bare_greek_name_is_reported_without_fix() {
+        let diagnostics = lint("2 pi r");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, "greek-without-percent");
+        assert_eq!(diagnostics[0].fix, None);
+        assert_eq!(apply_fixes("2 pi r", &diagnostics), "2 pi r");
+    }
+
+    #[test]
+    fn keyword_case_has_its_own_code() {
+        let diagnostics = lint("a OVER b");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, "keyword-case");
+        assert_eq!(apply_fixes("a OVER b", &diagnostics), "a over b");
+        assert_eq!(lint("%foo")[0].code, "unknown-command");
+    }
+
+    #[test]
+    fn braces_standing_for_a_fence_are_not_redundant() {
+        assert!(lint("left { a } b right )").is_empty());
+        assert_eq!(lint("{a} + b")[0].code, "redundant-braces");
+    }
+}
@@ -1,28 +1,1126 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
 use std::io::{self, IsTerminal, Read};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, SystemTime};
 
-use anyhow::Result;
-use clap::Parser;
+use anyhow::{Context, Result};
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use quick_xml::escape::escape;
+use rustyline::error::ReadlineError;
+use serde_json::{Value, json};
 
-use sm2mml::starmath_to_mathml;
+use sm2mml::{
+    Diagnostic, FormatOptions, IdentifierPolicy, Options, Profile, Severity, Violation,
+    apply_fixes, format_starmath_with_options, latex_to_starmath, lint, mathml_to_starmath, odf,
+    starmath_to_ast_json_with_options, starmath_to_latex_with_options,
+    starmath_to_mathml_with_options, starmath_to_omml_with_options, starmath_to_typst_with_options,
+    starmath_to_unicode_with_options, validate_with_profile,
+};
+
+// Exit statuses beyond 1, which stays for failures without a status of their own and for lint
+// or format findings. Clap already uses 2 for usage errors.
+const EXIT_PARSE: u8 = 3;
+const EXIT_IO: u8 = 4;
+const EXIT_VALIDATION: u8 = 5;
 
 #[derive(Parser)]
+#[command(version, about, args_conflicts_with_subcommands = true)]
 struct CLI {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// How errors and diagnostics are written to standard error
+    #[arg(long, value_enum, global = true, default_value_t = ErrorFormat::Human)]
+    error_format: ErrorFormat,
+    /// StarMath to convert, or - to read it from standard input
+    #[arg(conflicts_with = "files")]
     text: Option<String>,
+    /// Read StarMath from this file, - meaning standard input; may be repeated
+    #[arg(short = 'f', long = "file", value_name = "FILE")]
+    files: Vec<PathBuf>,
+    /// Write the output to this file instead of standard output
+    #[arg(short, long, conflicts_with = "write")]
+    output: Option<PathBuf>,
+    /// Write the output of each input file next to it, with the extension of the output format
+    #[arg(short, long, requires = "files")]
+    write: bool,
+    /// Check the generated MathML against the rules of MathML 3, or of MathML Core with
+    /// --to mathml-core
+    #[arg(long)]
+    validate: bool,
+    /// Convert each line of the input as a separate formula, writing JSON lines
+    #[arg(long, conflicts_with_all = ["jsonl", "write"])]
+    lines: bool,
+    /// Convert JSON lines holding an `id` and a `formula`, writing JSON lines
+    #[arg(long, conflicts_with = "write")]
+    jsonl: bool,
+    /// Format of the input
+    #[arg(long, value_enum, default_value_t = InputFormat::Starmath)]
+    from: InputFormat,
+    /// Format of the output
+    #[arg(long, value_enum, default_value_t = OutputFormat::Mathml)]
+    to: OutputFormat,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ErrorFormat {
+    /// Messages with the offending source underlined
+    Human,
+    /// One JSON object per line
+    Json,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum InputFormat {
+    Starmath,
+    /// MathML carrying a StarMath annotation, as written by LibreOffice
+    Mathml,
+    Latex,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    Mathml,
+    /// The MathML subset implemented by browsers
+    MathmlCore,
+    Latex,
+    /// Office Math Markup Language, as used by Word
+    Omml,
+    Unicode,
+    Typst,
+    /// The parsed syntax tree, as JSON
+    AstJson,
+}
+
+impl OutputFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Mathml | OutputFormat::MathmlCore => "mml",
+            OutputFormat::Latex => "tex",
+            OutputFormat::Omml => "xml",
+            OutputFormat::Unicode => "txt",
+            OutputFormat::Typst => "typ",
+            OutputFormat::AstJson => "json",
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Convert every formula embedded in an ODF package (.odt, .odf, ...) or flat ODF
+    /// document (.fodt, .fodf, ...)
+    Odf {
+        file: PathBuf,
+        /// Regenerate the MathML of each formula from its StarMath instead of printing it
+        #[arg(long)]
+        rewrite: bool,
+        /// Write the rewritten package here instead of replacing the input
+        #[arg(short, long, requires = "rewrite")]
+        output: Option<PathBuf>,
+    },
+    /// Rewrite StarMath files in the canonical style, or standard input to standard output
+    Fmt {
+        files: Vec<PathBuf>,
+        /// Only report inputs that are not formatted, exiting with status 1 if there are any
+        #[arg(long)]
+        check: bool,
+        /// Write Greek letters as %alpha instead of raw Unicode
+        #[arg(long)]
+        greek_names: bool,
+    },
+    /// Convert every .sm, .odf and .odt file under a directory, mirroring the tree into another
+    Convert {
+        dir: PathBuf,
+        /// Directory receiving the converted files
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Format written for .sm files, packages keep their own format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Mathml)]
+        to: OutputFormat,
+        /// Number of files converted at the same time, defaulting to the number of CPUs
+        #[arg(short, long)]
+        jobs: Option<usize>,
+    },
+    /// Regenerate the output of .sm files, or of those under directories, whenever they change
+    Watch {
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+        /// Format written next to each file
+        #[arg(long, value_enum, default_value_t = OutputFormat::Mathml)]
+        to: OutputFormat,
+        /// Milliseconds between two checks for changes
+        #[arg(long, default_value_t = 500)]
+        interval: u64,
+    },
+    /// Write a standalone HTML page showing each formula rendered next to its StarMath
+    Preview {
+        formulas: Vec<String>,
+        /// Also show the formula of this file; may be repeated
+        #[arg(short = 'f', long = "file", value_name = "FILE")]
+        files: Vec<PathBuf>,
+        /// Write the page here instead of standard output
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Convert formulas typed one at a time, with `:set` commands to change the output
+    Repl,
+    /// Print the completion script of a shell
+    Completions { shell: Shell },
+    /// Print the manual page, in roff
+    Man,
+    /// Report questionable StarMath in files, or in standard input
+    Lint {
+        files: Vec<PathBuf>,
+        /// Apply the suggested fixes, rewriting the files or printing the fixed input
+        #[arg(long)]
+        fix: bool,
+    },
+}
+
+fn main() -> ExitCode {
+    let cli = CLI::parse();
+    match run(&cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => report(&error, cli.error_format),
+    }
+}
+
+/// The input is not a formula that can be converted, with what points at the problem.
+#[derive(Debug)]
+struct ParseError {
+    name: String,
+    source: String,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.diagnostics.is_empty() {
+            return write!(f, "Cannot convert {}", self.name);
+        }
+        let messages: Vec<_> = self
+            .diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.message.as_str())
+            .collect();
+        write!(f, "{}", messages.join("; "))
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// The generated MathML breaks the structural rules.
+#[derive(Debug)]
+struct ValidationError(Vec<Violation>);
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Generated MathML has {} violation(s)", self.0.len())
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+/// A run that completed but found problems, such as lint findings or failed files, with the
+/// status to exit with.
+#[derive(Debug)]
+struct Failed {
+    code: &'static str,
+    message: String,
+    status: u8,
 }
 
-fn main() -> Result<()> {
-    let content = if let Some(text) = CLI::parse().text {
-        text
+impl fmt::Display for Failed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for Failed {}
+
+fn report(error: &anyhow::Error, format: ErrorFormat) -> ExitCode {
+    print_failure(error, format);
+    ExitCode::from(exit_status(error))
+}
+
+fn exit_status(error: &anyhow::Error) -> u8 {
+    if let Some(failed) = error.downcast_ref::<Failed>() {
+        failed.status
+    } else if error.downcast_ref::<ParseError>().is_some() {
+        EXIT_PARSE
+    } else if error.downcast_ref::<ValidationError>().is_some() {
+        EXIT_VALIDATION
+    } else if error.chain().any(|cause| cause.is::<io::Error>()) {
+        EXIT_IO
     } else {
+        1
+    }
+}
+
+fn print_failure(error: &anyhow::Error, format: ErrorFormat) {
+    if let Some(failed) = error.downcast_ref::<Failed>() {
+        print_error(failed.code, None, &failed.message, format);
+    } else if let Some(parse) = error.downcast_ref::<ParseError>() {
+        if parse.diagnostics.is_empty() {
+            print_error("parse", None, &format!("{:#}", error), format);
+        }
+        for diagnostic in &parse.diagnostics {
+            print_diagnostic(&parse.name, &parse.source, diagnostic, format);
+        }
+    } else if let Some(ValidationError(violations)) = error.downcast_ref::<ValidationError>() {
+        for violation in violations {
+            print_error(
+                "validation",
+                Some(&violation.path),
+                &violation.message,
+                format,
+            );
+        }
+        print_error("validation", None, &error.to_string(), format);
+    } else if error.chain().any(|cause| cause.is::<io::Error>()) {
+        print_error("io", None, &format!("{:#}", error), format);
+    } else {
+        print_error("error", None, &format!("{:#}", error), format);
+    }
+}
+
+// An error without a location in the source, the path pointing into the generated MathML
+fn print_error(code: &str, path: Option<&str>, message: &str, format: ErrorFormat) {
+    match format {
+        ErrorFormat::Human => match path {
+            Some(path) => eprintln!("error[{}]: {}: {}", code, path, message),
+            None => eprintln!("error: {}", message),
+        },
+        ErrorFormat::Json => {
+            let error =
+                json!({ "severity": "error", "code": code, "path": path, "message": message });
+            eprintln!("{}", error);
+        }
+    }
+}
+
+fn run(cli: &CLI) -> Result<()> {
+    let format = cli.error_format;
+    match &cli.command {
+        None => convert_inputs(cli),
+        Some(Command::Odf {
+            file,
+            rewrite: false,
+            ..
+        }) => convert_package(file),
+        Some(Command::Odf {
+            file,
+            rewrite: true,
+            output,
+        }) => {
+            let options = Options::default();
+            let count = match output {
+                Some(output) => odf::rewrite_formulas(file, output, &options)?,
+                None => odf::rewrite_formulas_in_place(file, &options)?,
+            };
+            eprintln!("Rewrote {} formula(s)", count);
+            Ok(())
+        }
+        Some(Command::Fmt {
+            files,
+            check,
+            greek_names,
+        }) => {
+            let options = FormatOptions {
+                greek_names: *greek_names,
+            };
+            format_files(files, *check, &options, format)
+        }
+        Some(Command::Convert {
+            dir,
+            output,
+            to,
+            jobs,
+        }) => convert_dir(dir, output, *to, *jobs, format),
+        Some(Command::Watch {
+            paths,
+            to,
+            interval,
+        }) => watch(paths, *to, Duration::from_millis(*interval), format),
+        Some(Command::Preview {
+            formulas,
+            files,
+            output,
+        }) => {
+            if let Some(output) = output {
+                refuse_overwrite(output, files)?;
+            }
+            let page = preview(formulas, files)?;
+            match output {
+                Some(path) => fs::write(path, page)?,
+                None => print!("{}", page),
+            }
+            Ok(())
+        }
+        Some(Command::Repl) => repl(),
+        Some(Command::Completions { shell }) => {
+            clap_complete::generate(*shell, &mut CLI::command(), "sm2mml", &mut io::stdout());
+            Ok(())
+        }
+        Some(Command::Man) => Ok(clap_mangen::Man::new(CLI::command()).render(&mut io::stdout())?),
+        Some(Command::Lint { files, fix }) => lint_files(files, *fix, format),
+    }
+}
+
+// Converts the formula given as argument, the files or standard input
+fn convert_inputs(cli: &CLI) -> Result<()> {
+    if let Some(output) = &cli.output {
+        refuse_overwrite(output, &cli.files)?;
+    }
+    if cli.validate && !matches!(cli.to, OutputFormat::Mathml | OutputFormat::MathmlCore) {
+        anyhow::bail!("--validate only applies to MathML output");
+    }
+    let inputs = read_inputs(cli)?;
+    let mut outputs = Vec::new();
+    // Exit statuses of the batch records that failed
+    let mut failures = Vec::new();
+    for (file, content) in &inputs {
+        if cli.lines || cli.jsonl {
+            outputs.extend(convert_batch(content, cli, &mut failures));
+            continue;
+        }
+        let name = file
+            .as_ref()
+            .map_or("<stdin>".to_string(), |file| file.display().to_string());
+        let starmath = import(&name, content, cli.from)?;
+        let output = convert(&name, &starmath, cli.to, cli.validate)?;
+        match file {
+            Some(file) if cli.write => {
+                let path = file.with_extension(cli.to.extension());
+                if &path == file {
+                    anyhow::bail!("Refusing to overwrite the input {}", file.display());
+                }
+                fs::write(&path, output + "\n")?;
+                eprintln!("Wrote {}", path.display());
+            }
+            _ => outputs.push(output),
+        }
+    }
+
+    if outputs.is_empty() {
+        return Ok(());
+    }
+    let mut text = outputs.join("\n");
+    text.push('\n');
+    match &cli.output {
+        Some(path) => fs::write(path, text)?,
+        None => print!("{}", text),
+    }
+    if !failures.is_empty() {
+        let message = format!("{} record(s) failed to convert", failures.len());
+        return Err(batch_failed(&failures, message));
+    }
+    Ok(())
+}
+
+// Writing over an input would lose it, however either path is spelled
+fn refuse_overwrite(output: &Path, inputs: &[PathBuf]) -> Result<()> {
+    // An output that does not exist yet cannot be one of the inputs
+    let Ok(output) = fs::canonicalize(output) else {
+        return Ok(());
+    };
+    for input in inputs {
+        if fs::canonicalize(input).is_ok_and(|input| input == output) {
+            anyhow::bail!("Refusing to overwrite the input {}", input.display());
+        }
+    }
+    Ok(())
+}
+
+// Each input with the file it comes from, standard input having none
+fn read_inputs(cli: &CLI) -> Result<Vec<(Option<PathBuf>, String)>> {
+    let stdin = || -> Result<String> {
+        let mut content = String::new();
+        io::stdin().read_to_string(&mut content)?;
+        Ok(content)
+    };
+
+    if let Some(text) = &cli.text {
+        let content = if text == "-" { stdin()? } else { text.clone() };
+        return Ok(vec![(None, content)]);
+    }
+    if cli.files.is_empty() {
         if io::stdin().is_terminal() {
             anyhow::bail!("No input provided. Use -f <file> or pipe StarMath expression.");
         }
+        return Ok(vec![(None, stdin()?)]);
+    }
+    cli.files
+        .iter()
+        .map(|file| {
+            if file.as_os_str() == "-" {
+                Ok((None, stdin()?))
+            } else {
+                let content = fs::read_to_string(file)
+                    .with_context(|| format!("Cannot read {}", file.display()))?;
+                Ok((Some(file.clone()), content))
+            }
+        })
+        .collect()
+}
+
+// StarMath of an input, whose structural errors are refused with their location
+fn import(name: &str, content: &str, from: InputFormat) -> Result<String> {
+    let parse_error = |diagnostics| ParseError {
+        name: name.to_string(),
+        source: content.to_string(),
+        diagnostics,
+    };
+    let starmath = match from {
+        InputFormat::Starmath => content.trim().to_string(),
+        InputFormat::Mathml => mathml_to_starmath(content).context(parse_error(Vec::new()))?,
+        InputFormat::Latex => latex_to_starmath(content.trim()).context(parse_error(Vec::new()))?,
+    };
+    if from == InputFormat::Starmath {
+        check_structure(name, content)?;
+    }
+    Ok(starmath)
+}
+
+// Refuses StarMath with structural errors such as unbalanced braces, pointing at them
+fn check_structure(name: &str, source: &str) -> Result<()> {
+    let errors: Vec<_> = lint(source)
+        .into_iter()
+        .filter(|diagnostic| diagnostic.severity == Severity::Error)
+        .collect();
+    if errors.is_empty() {
+        return Ok(());
+    }
+    Err(ParseError {
+        name: name.to_string(),
+        source: source.to_string(),
+        diagnostics: errors,
+    }
+    .into())
+}
+
+// One JSON line per record, a failing record reporting its error instead of stopping the batch
+// and adding its exit status to failures
+fn convert_batch(content: &str, cli: &CLI, failures: &mut Vec<u8>) -> Vec<String> {
+    let mut records = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (id, formula) = if cli.jsonl {
+            read_record(line)
+        } else {
+            (json!(index + 1), Ok(line.to_string()))
+        };
+        let name = format!("<line {}>", index + 1);
+        let result = formula
+            .and_then(|formula| import(&name, &formula, cli.from))
+            .and_then(|starmath| convert(&name, &starmath, cli.to, cli.validate));
+        let record = match result {
+            Ok(output) => json!({ "id": id, "output": output }),
+            Err(error) => {
+                failures.push(exit_status(&error));
+                json!({ "id": id, "line": index + 1, "error": format!("{:#}", error) })
+            }
+        };
+        records.push(record.to_string());
+    }
+    records
+}
+
+// The id of a JSON line record, null when missing, and its formula
+fn read_record(line: &str) -> (Value, Result<String>) {
+    let mut record: Value = match serde_json::from_str(line) {
+        Ok(record) => record,
+        Err(error) => return (Value::Null, Err(error).context("Invalid JSON")),
+    };
+    let id = record.get_mut("id").map(Value::take).unwrap_or_default();
+    match record.get_mut("formula").map(Value::take) {
+        Some(Value::String(formula)) => (id, Ok(formula)),
+        _ => (id, Err(anyhow::anyhow!("Record has no formula string"))),
+    }
+}
+
+fn convert(name: &str, starmath: &str, to: OutputFormat, check: bool) -> Result<String> {
+    convert_with_options(name, starmath, to, &Options::default(), check)
+}
+
+fn convert_with_options(
+    name: &str,
+    starmath: &str,
+    to: OutputFormat,
+    options: &Options,
+    check: bool,
+) -> Result<String> {
+    let output = match to {
+        OutputFormat::Mathml => starmath_to_mathml_with_options(starmath, options),
+        OutputFormat::MathmlCore => {
+            let options = Options {
+                profile: Profile::Core,
+                ..options.clone()
+            };
+            starmath_to_mathml_with_options(starmath, &options)
+        }
+        OutputFormat::Latex => starmath_to_latex_with_options(starmath, options),
+        OutputFormat::Omml => starmath_to_omml_with_options(starmath, options),
+        OutputFormat::Unicode => starmath_to_unicode_with_options(starmath, options),
+        OutputFormat::Typst => starmath_to_typst_with_options(starmath, options),
+        OutputFormat::AstJson => starmath_to_ast_json_with_options(starmath, options),
+    };
+    let output = output.map_err(|error| {
+        error.context(ParseError {
+            name: name.to_string(),
+            source: starmath.to_string(),
+            diagnostics: Vec::new(),
+        })
+    })?;
+    if check {
+        let profile = match to {
+            OutputFormat::MathmlCore => Profile::Core,
+            _ => Profile::MathMl3,
+        };
+        let violations = validate_with_profile(&output, profile)?;
+        if !violations.is_empty() {
+            return Err(ValidationError(violations).into());
+        }
+    }
+    Ok(output)
+}
+
+fn format_files(
+    files: &[PathBuf],
+    check: bool,
+    options: &FormatOptions,
+    format: ErrorFormat,
+) -> Result<()> {
+    if files.is_empty() {
+        let mut content = String::new();
+        io::stdin().read_to_string(&mut content)?;
+        let formatted = format_starmath_with_options(content.trim(), options)?;
+        if !check {
+            println!("{}", formatted);
+        } else if formatted != content.trim_end() {
+            return Err(not_formatted(1));
+        }
+        return Ok(());
+    }
+
+    let mut unformatted = 0;
+    for file in files {
+        let content = fs::read_to_string(file)?;
+        let formatted = format_starmath_with_options(content.trim(), options)? + "\n";
+        if formatted == content {
+            continue;
+        }
+        unformatted += 1;
+        if check {
+            let message = format!("{} is not formatted", file.display());
+            print_error("unformatted", None, &message, format);
+        } else {
+            fs::write(file, formatted)?;
+        }
+    }
+    if check && unformatted > 0 {
+        return Err(not_formatted(unformatted));
+    }
+    if !check {
+        eprintln!("Formatted {} file(s)", unformatted);
+    }
+    Ok(())
+}
+
+fn not_formatted(count: usize) -> anyhow::Error {
+    Failed {
+        code: "unformatted",
+        message: format!("{} input(s) not formatted", count),
+        status: 1,
+    }
+    .into()
+}
+
+fn lint_files(files: &[PathBuf], fix: bool, format: ErrorFormat) -> Result<()> {
+    let mut inputs = Vec::new();
+    if files.is_empty() {
         let mut content = String::new();
         io::stdin().read_to_string(&mut content)?;
-        content
+        inputs.push(("<stdin>".to_string(), None, content));
+    }
+    for file in files {
+        let content = fs::read_to_string(file)?;
+        inputs.push((file.display().to_string(), Some(file), content));
+    }
+
+    let mut reported = 0;
+    for (name, file, mut content) in inputs {
+        if fix {
+            content = apply_fixes(&content, &lint(&content));
+            match file {
+                Some(file) => fs::write(file, &content)?,
+                None => print!("{}", content),
+            }
+        }
+        reported += print_diagnostics(&name, &content, format);
+    }
+    if reported > 0 {
+        return Err(Failed {
+            code: "lint",
+            message: format!("{} problem(s) found", reported),
+            status: 1,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+// Prints the lint diagnostics of a source to standard error, returning how many there were
+fn print_diagnostics(name: &str, content: &str, format: ErrorFormat) -> usize {
+    let diagnostics = lint(content);
+    for diagnostic in &diagnostics {
+        print_diagnostic(name, content, diagnostic, format);
+    }
+    diagnostics.len()
+}
+
+fn print_diagnostic(name: &str, source: &str, diagnostic: &Diagnostic, format: ErrorFormat) {
+    let (line, column) = line_column(source, diagnostic.span.start);
+    match format {
+        ErrorFormat::Human => {
+            eprintln!(
+                "{}[{}]: {}",
+                diagnostic.severity, diagnostic.code, diagnostic.message
+            );
+            eprintln!("{}", underline(name, source, diagnostic.span.clone()));
+        }
+        ErrorFormat::Json => {
+            let diagnostic = json!({
+                "file": name,
+                "severity": diagnostic.severity.to_string(),
+                "code": diagnostic.code,
+                "message": diagnostic.message,
+                "span": { "start": diagnostic.span.start, "end": diagnostic.span.end },
+                "line": line,
+                "column": column,
+            });
+            eprintln!("{}", diagnostic);
+        }
+    }
+}
+
+// The location of a span followed by its source line, with carets under the span
+fn underline(name: &str, source: &str, span: Range<usize>) -> String {
+    let (line, column) = line_column(source, span.start);
+    let line_start = source[..span.start]
+        .rfind('\n')
+        .map_or(0, |newline| newline + 1);
+    let line_end = source[span.start..]
+        .find('\n')
+        .map_or(source.len(), |newline| span.start + newline);
+    let width = source[span.start..span.end.clamp(span.start, line_end)]
+        .chars()
+        .count()
+        .max(1);
+    let number = line.to_string();
+    let gutter = " ".repeat(number.len());
+    format!(
+        "{gutter}--> {name}:{line}:{column}\n{gutter} |\n{number} | {}\n{gutter} | {}{}",
+        &source[line_start..line_end],
+        " ".repeat(column - 1),
+        "^".repeat(width)
+    )
+}
+
+// One-based line and column of a byte offset, counting columns in characters
+fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    (line, before[line_start..].chars().count() + 1)
+}
+
+fn convert_dir(
+    dir: &Path,
+    output: &Path,
+    to: OutputFormat,
+    jobs: Option<usize>,
+    format: ErrorFormat,
+) -> Result<()> {
+    let canonical_output = fs::canonicalize(output).ok();
+    if canonical_output.is_some() && canonical_output == fs::canonicalize(dir).ok() {
+        anyhow::bail!(
+            "The output directory {} is the input directory",
+            output.display()
+        );
+    }
+    let mut files = Vec::new();
+    find_convertible(dir, canonical_output.as_deref(), &mut files)?;
+    let jobs = match jobs {
+        Some(jobs) => jobs.max(1),
+        None => thread::available_parallelism().map_or(1, usize::from),
     };
 
-    let output = starmath_to_mathml(&content.trim())?;
-    println!("{}", output);
+    // Workers take the next file from a shared counter until none are left
+    let next = AtomicUsize::new(0);
+    let failures = Mutex::new(Vec::new());
+    thread::scope(|scope| {
+        for _ in 0..jobs.min(files.len()) {
+            scope.spawn(|| {
+                while let Some(file) = files.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    if let Err(error) = convert_file(file, dir, output, to) {
+                        let error = error.context(format!("Cannot convert {}", file.display()));
+                        print_failure(&error, format);
+                        failures.lock().unwrap().push(exit_status(&error));
+                    }
+                }
+            });
+        }
+    });
+
+    let statuses = failures.into_inner().unwrap();
+    let failed = statuses.len();
+    eprintln!(
+        "Converted {} file(s), {} failed",
+        files.len() - failed,
+        failed
+    );
+    if failed > 0 {
+        let message = format!("{} file(s) failed to convert", failed);
+        return Err(batch_failed(&statuses, message));
+    }
+    Ok(())
+}
+
+// Failure of a batch of conversions, which keeps the status of its failures if they are all of
+// one kind
+fn batch_failed(statuses: &[u8], message: String) -> anyhow::Error {
+    let status = match statuses.first() {
+        Some(&first) if statuses.iter().all(|&status| status == first) => first,
+        _ => 1,
+    };
+    Failed {
+        code: "convert",
+        message,
+        status,
+    }
+    .into()
+}
+
+// Convertible files under dir in path order, leaving out the output directory
+fn find_convertible(dir: &Path, output: Option<&Path>, files: &mut Vec<PathBuf>) -> Result<()> {
+    let mut entries = fs::read_dir(dir)
+        .with_context(|| format!("Cannot read {}", dir.display()))?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<Result<Vec<_>>>()?;
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            if fs::canonicalize(&path).ok().as_deref() != output {
+                find_convertible(&path, output, files)?;
+            }
+        } else if path
+            .extension()
+            .is_some_and(|extension| ["sm", "odf", "odt"].contains(&&*extension.to_string_lossy()))
+        {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+// Packages are rewritten with regenerated MathML, StarMath files converted to the chosen format
+fn convert_file(file: &Path, dir: &Path, output: &Path, to: OutputFormat) -> Result<()> {
+    let mut target = output.join(file.strip_prefix(dir)?);
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if file.extension().is_some_and(|extension| extension == "sm") {
+        let content = fs::read_to_string(file)?;
+        target.set_extension(to.extension());
+        let name = file.display().to_string();
+        check_structure(&name, &content)?;
+        fs::write(&target, convert(&name, content.trim(), to, false)? + "\n")?;
+        return Ok(());
+    }
+
+    // Written aside first, so a failure leaves whatever was at the target untouched
+    let file_name = target.file_name().unwrap_or_default().to_string_lossy();
+    let temporary = target.with_file_name(format!(".{}.sm2mml", file_name));
+    match odf::rewrite_formulas(file, &temporary, &Options::default()) {
+        Ok(_) => fs::rename(&temporary, &target)?,
+        Err(error) => {
+            let _ = fs::remove_file(&temporary);
+            return Err(error);
+        }
+    }
+    Ok(())
+}
+
+// Polls modification times, so it also works where no file system notifications exist
+fn watch(
+    paths: &[PathBuf],
+    to: OutputFormat,
+    interval: Duration,
+    format: ErrorFormat,
+) -> Result<()> {
+    for path in paths {
+        if !path.is_dir() && path.extension().is_none_or(|extension| extension != "sm") {
+            anyhow::bail!("{} is neither a directory nor a .sm file", path.display());
+        }
+    }
+
+    let mut seen: HashMap<PathBuf, SystemTime> = HashMap::new();
+    loop {
+        let mut files = Vec::new();
+        for path in paths {
+            if path.is_dir() {
+                find_convertible(path, None, &mut files)?;
+            } else {
+                files.push(path.clone());
+            }
+        }
+        files.retain(|file| file.extension().is_some_and(|extension| extension == "sm"));
+        seen.retain(|file, _| files.contains(file));
+
+        for file in files {
+            // A file being replaced may briefly be missing
+            let Ok(modified) = fs::metadata(&file).and_then(|metadata| metadata.modified()) else {
+                continue;
+            };
+            if seen.insert(file.clone(), modified) == Some(modified) {
+                continue;
+            }
+            if let Err(error) = regenerate(&file, to, format) {
+                let error = error.context(format!("Cannot convert {}", file.display()));
+                print_failure(&error, format);
+            }
+        }
+        thread::sleep(interval);
+    }
+}
+
+fn regenerate(file: &Path, to: OutputFormat, format: ErrorFormat) -> Result<()> {
+    let content = fs::read_to_string(file)?;
+    let name = file.display().to_string();
+    print_diagnostics(&name, &content, format);
+    // The errors were just printed, the output waits for them to be fixed
+    if check_structure(&name, &content).is_err() {
+        return Ok(());
+    }
+    let path = file.with_extension(to.extension());
+    let output = convert(&name, content.trim(), to, false)?;
+    fs::write(&path, output + "\n")?;
+    eprintln!("Wrote {}", path.display());
+    Ok(())
+}
+
+const REPL_HELP: &str = "Type a StarMath formula to convert it. Commands:
+  :set to <format>               mathml, mathml-core, latex, omml, unicode, typst or ast-json
+  :set display <block|inline>    display attribute of the MathML
+  :set letters <on|off>          split words like abc into the letters a, b and c
+  :set invisible-times <on|off>  mark juxtaposed operands as multiplied
+  :show                          print the current settings
+  :help                          print this help
+  :quit                          leave, as does Ctrl-D";
+
+fn repl() -> Result<()> {
+    let mut editor = rustyline::DefaultEditor::new()?;
+    let history = std::env::var_os("HOME").map(|home| Path::new(&home).join(".sm2mml_history"));
+    if let Some(history) = &history {
+        let _ = editor.load_history(history);
+    }
+
+    let mut to = OutputFormat::Mathml;
+    let mut options = Options::default();
+    println!("{}", REPL_HELP);
+    loop {
+        let line = match editor.readline("sm2mml> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(error) => return Err(error.into()),
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        editor.add_history_entry(line)?;
+
+        if let Some(command) = line.strip_prefix(':') {
+            let words: Vec<_> = command.split_whitespace().collect();
+            match words.as_slice() {
+                ["quit" | "q"] => break,
+                ["help" | "h"] => println!("{}", REPL_HELP),
+                ["show"] => println!(
+                    "to {}, display {}, letters {}, invisible-times {}",
+                    to.to_possible_value()
+                        .map_or(String::new(), |value| value.get_name().to_string()),
+                    if options.inline { "inline" } else { "block" },
+                    on_off(options.identifiers == IdentifierPolicy::Letters),
+                    on_off(options.invisible_times)
+                ),
+                ["set", setting, value] => {
+                    if let Err(error) = set(&mut to, &mut options, setting, value) {
+                        eprintln!("error: {}", error);
+                    }
+                }
+                _ => eprintln!("error: unknown command :{}, see :help", command),
+            }
+            continue;
+        }
+
+        print_diagnostics("<input>", line, ErrorFormat::Human);
+        // Errors such as unbalanced braces were just shown, nothing to convert
+        if check_structure("<input>", line).is_err() {
+            continue;
+        }
+        match convert_with_options("<input>", line, to, &options, false) {
+            Ok(output) => println!("{}", output),
+            Err(error) => eprintln!("error: {:#}", error),
+        }
+    }
+
+    if let Some(history) = &history {
+        let _ = editor.save_history(history);
+    }
+    Ok(())
+}
+
+fn set(to: &mut OutputFormat, options: &mut Options, setting: &str, value: &str) -> Result<()> {
+    let switch = || match value {
+        "on" => Ok(true),
+        "off" => Ok(false),
+        _ => Err(anyhow::anyhow!("expected on or off, not {}", value)),
+    };
+    match setting {
+        "to" => *to = OutputFormat::from_str(value, true).map_err(anyhow::Error::msg)?,
+        "display" => {
+            options.inline = match value {
+                "inline" => true,
+                "block" => false,
+                _ => anyhow::bail!("expected block or inline, not {}", value),
+            }
+        }
+        "letters" => {
+            options.identifiers = if switch()? {
+                IdentifierPolicy::Letters
+            } else {
+                IdentifierPolicy::Word
+            }
+        }
+        "invisible-times" => options.invisible_times = switch()?,
+        _ => anyhow::bail!("unknown setting {}, see :help", setting),
+    }
+    Ok(())
+}
+
+fn on_off(enabled: bool) -> &'static str {
+    if enabled { "on" } else { "off" }
+}
+
+const PREVIEW_STYLE: &str = "body { font-family: sans-serif; margin: 2em; }
+section { border-top: 1px solid #ccc; padding: 1em 0; }
+h2 { font-size: 1em; color: #555; }
+math { font-size: 1.5em; }
+pre { background: #f4f4f4; padding: 0.5em; white-space: pre-wrap; }
+.warning { color: #8a6d00; }
+.error { color: #b00020; }";
+
+// Browsers render MathML Core natively, so that profile is embedded
+fn preview(formulas: &[String], files: &[PathBuf]) -> Result<String> {
+    let mut inputs: Vec<_> = formulas
+        .iter()
+        .enumerate()
+        .map(|(index, formula)| (format!("Formula {}", index + 1), formula.clone()))
+        .collect();
+    for file in files {
+        let content =
+            fs::read_to_string(file).with_context(|| format!("Cannot read {}", file.display()))?;
+        inputs.push((file.display().to_string(), content));
+    }
+    if inputs.is_empty() {
+        anyhow::bail!("No formula to preview");
+    }
+
+    let mut sections = String::new();
+    for (name, content) in &inputs {
+        let starmath = content.trim();
+        let mut diagnostics: Vec<_> = lint(starmath)
+            .into_iter()
+            .map(|diagnostic| {
+                let (line, column) = line_column(starmath, diagnostic.span.start);
+                let text = format!(
+                    "{}:{}: {}[{}]: {}",
+                    line, column, diagnostic.severity, diagnostic.code, diagnostic.message
+                );
+                (diagnostic.severity.to_string(), text)
+            })
+            .collect();
+        // Structural errors are already listed, such a formula is shown without rendering
+        let rendered = if check_structure(name, starmath).is_err() {
+            String::new()
+        } else {
+            match convert(name, starmath, OutputFormat::MathmlCore, false) {
+                Ok(mathml) => {
+                    let start = mathml.find("<math").unwrap_or(0);
+                    mathml[start..].to_string()
+                }
+                Err(error) => {
+                    diagnostics.push(("error".to_string(), format!("{:#}", error)));
+                    String::new()
+                }
+            }
+        };
+        let mut list = String::new();
+        if !diagnostics.is_empty() {
+            let items: String = diagnostics
+                .iter()
+                .map(|(severity, text)| {
+                    format!("<li class=\"{}\">{}</li>\n", severity, escape(text))
+                })
+                .collect();
+            list = format!("<ul>\n{}</ul>\n", items);
+        }
+        sections.push_str(&format!(
+            "<section>\n<h2>{}</h2>\n{}\n<pre>{}</pre>\n{}</section>\n",
+            escape(name),
+            rendered,
+            escape(starmath),
+            list
+        ));
+    }
+    Ok(format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>sm2mml preview</title>\n<style>\n{}\n</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+        PREVIEW_STYLE, sections
+    ))
+}
+
+// Prints a JSON manifest mapping each formula object of the document to its MathML
+fn convert_package(file: &Path) -> Result<()> {
+    let options = Options::default();
+    let is_flat = file
+        .extension()
+        .is_some_and(|extension| extension.to_string_lossy().starts_with("fod"));
+
+    let manifest: Vec<_> = if is_flat {
+        odf::read_flat_formulas(file)?
+            .iter()
+            .map(|formula| {
+                let mathml = formula.to_mathml(&options)?;
+                Ok(json!({ "frame": formula.frame, "mathml": mathml }))
+            })
+            .collect::<Result<_>>()?
+    } else {
+        odf::convert_formulas(file, &options)?
+            .into_iter()
+            .map(|(path, mathml)| json!({ "path": path, "mathml": mathml }))
+            .collect()
+    };
+    println!("{}", serde_json::to_string_pretty(&manifest)?);
     Ok(())
 }
@@ -0,0 +1,274 @@
+use anyhow::Result;
+use quick_xml::Writer;
+use quick_xml::events::{BytesEnd, BytesStart, BytesText, Event};
+use std::io::Cursor;
+
+use crate::alphanumeric::{self, Variant};
+use crate::ast::{Font, Node, is_integral};
+use crate::{OperatorKind, Profile};
+
+type XmlWriter = Writer<Cursor<Vec<u8>>>;
+
+pub(crate) fn qualified_name(prefix: Option<&str>, name: &str) -> String {
+    match prefix {
+        Some(prefix) => format!("{}:{}", prefix, name),
+        None => name.to_string(),
+    }
+}
+
+pub(crate) struct MathmlWriter<'a> {
+    writer: &'a mut XmlWriter,
+    profile: Profile,
+    prefix: Option<&'a str>,
+}
+
+impl<'a> MathmlWriter<'a> {
+    pub(crate) fn new(
+        writer: &'a mut XmlWriter,
+        profile: Profile,
+        prefix: Option<&'a str>,
+    ) -> Self {
+        MathmlWriter {
+            writer,
+            profile,
+            prefix,
+        }
+    }
+
+    fn start(&self, name: &str) -> BytesStart<'static> {
+        BytesStart::new(qualified_name(self.prefix, name))
+    }
+
+    fn end(&self, name: &str) -> BytesEnd<'static> {
+        BytesEnd::new(qualified_name(self.prefix, name))
+    }
+
+    // Writes the children of a row directly into an element that is already an mrow
+    pub(crate) fn write_children(&mut self, node: &Node) -> Result<()> {
+        self.write_row_children(node, Font::default())
+    }
+
+    fn write_row_children(&mut self, node: &Node, font: Font) -> Result<()> {
+        match node {
+            Node::Row(children) => self.write_sequence(children, font),
+            _ => self.write_node(node, font),
+        }
+    }
+
+    // Splits the sequence at its loosest infix operators, so the operands of tighter ones get
+    // an mrow of their own: a + b × c = d becomes {a + {b × c}} = d
+    fn write_sequence(&mut self, children: &[Node], font: Font) -> Result<()> {
+        let infix = infix_operators(children);
+        let loosest = infix.iter().map(|&(_, precedence)| precedence).min();
+        let Some(loosest) = loosest.filter(|&p| infix.iter().any(|&(_, q)| q != p)) else {
+            for child in children {
+                self.write_node(child, font)?;
+            }
+            return Ok(());
+        };
+
+        let mut start = 0;
+        for &(index, precedence) in &infix {
+            if precedence == loosest {
+                self.write_operand(&children[start..index], font)?;
+                self.write_node(&children[index], font)?;
+                start = index + 1;
+            }
+        }
+        self.write_operand(&children[start..], font)
+    }
+
+    fn write_operand(&mut self, children: &[Node], font: Font) -> Result<()> {
+        match children {
+            [] => Ok(()),
+            [child] => self.write_node(child, font),
+            _ => {
+                self.writer.write_event(Event::Start(self.start("mrow")))?;
+                self.write_sequence(children, font)?;
+                self.writer.write_event(Event::End(self.end("mrow")))?;
+                Ok(())
+            }
+        }
+    }
+
+    // Every node is written as exactly one element, so parents always get the arity they expect
+    fn write_node(&mut self, node: &Node, font: Font) -> Result<()> {
+        match node {
+            Node::Number(number) => self.write_styled("mn", number, false, font),
+            Node::Identifier(name) => self.write_styled("mi", name, true, font),
+            Node::Function(name) => self.write_styled("mi", name, false, font),
+            Node::Operator { symbol, kind, .. } => {
+                let mut mo = self.start("mo");
+                match kind {
+                    Some(OperatorKind::Binary) => mo.push_attribute(("form", "infix")),
+                    Some(OperatorKind::Unary) => mo.push_attribute(("form", "prefix")),
+                    None => {}
+                }
+                if symbol == "=" || symbol == "∑" {
+                    mo.push_attribute(("stretchy", "false"));
+                }
+                self.write_token(mo, symbol)
+            }
+            Node::Text(text) => self.write_styled("mtext", text, false, font),
+            Node::Row(children) if children.len() == 1 => self.write_node(&children[0], font),
+            Node::Row(_) => self.write_mrow(node, font),
+            Node::Sub(base, sub) => self.write_parent("msub", &[base, sub], font),
+            Node::Sup(base, sup) => self.write_parent("msup", &[base, sup], font),
+            Node::Frac(num, den) => self.write_parent("mfrac", &[num, den], font),
+            Node::Sqrt(body) => self.write_parent("msqrt", &[body], font),
+            Node::Root(index, body) => self.write_parent("mroot", &[body, index], font),
+            Node::Accent(base, accent) => {
+                let mut mover = self.start("mover");
+                mover.push_attribute(("accent", "true"));
+                self.writer.write_event(Event::Start(mover))?;
+                self.write_node(base, font)?;
+                let mut mo = self.start("mo");
+                mo.push_attribute(("stretchy", "false"));
+                self.write_token(mo, accent)?;
+                self.writer.write_event(Event::End(self.end("mover")))?;
+                Ok(())
+            }
+            Node::Fenced { open, body, close } => {
+                self.writer.write_event(Event::Start(self.start("mrow")))?;
+                self.write_fence(open, "prefix")?;
+                self.write_mrow(body, font)?;
+                if let Some(close) = close {
+                    self.write_fence(close, "postfix")?;
+                }
+                self.writer.write_event(Event::End(self.end("mrow")))?;
+                Ok(())
+            }
+            Node::Styled(style, body) => self.write_node(body, font.with(*style)),
+            Node::LargeOp {
+                symbol,
+                from,
+                to,
+                body,
+            } => {
+                if body.is_some() {
+                    self.writer.write_event(Event::Start(self.start("mrow")))?;
+                }
+                let operator = Node::operator(symbol);
+                let integral = is_integral(symbol);
+                match (from, to) {
+                    (None, None) => self.write_node(&operator, font)?,
+                    (Some(from), None) => {
+                        let name = if integral { "msub" } else { "munder" };
+                        self.write_parent(name, &[&operator, from], font)?
+                    }
+                    (None, Some(to)) => {
+                        let name = if integral { "msup" } else { "mover" };
+                        self.write_parent(name, &[&operator, to], font)?
+                    }
+                    (Some(from), Some(to)) => {
+                        let name = if integral { "msubsup" } else { "munderover" };
+                        self.write_parent(name, &[&operator, from, to], font)?
+                    }
+                }
+                if let Some(body) = body {
+                    self.write_node(body, font)?;
+                    self.writer.write_event(Event::End(self.end("mrow")))?;
+                }
+                Ok(())
+            }
+            Node::Matrix(rows) => {
+                self.writer
+                    .write_event(Event::Start(self.start("mtable")))?;
+                for row in rows {
+                    self.writer.write_event(Event::Start(self.start("mtr")))?;
+                    for cell in row {
+                        // mtd behaves like an mrow, so cells need no extra wrapping
+                        self.writer.write_event(Event::Start(self.start("mtd")))?;
+                        self.write_row_children(cell, font)?;
+                        self.writer.write_event(Event::End(self.end("mtd")))?;
+                    }
+                    self.writer.write_event(Event::End(self.end("mtr")))?;
+                }
+                self.writer.write_event(Event::End(self.end("mtable")))?;
+                Ok(())
+            }
+        }
+    }
+
+    fn write_mrow(&mut self, node: &Node, font: Font) -> Result<()> {
+        self.writer.write_event(Event::Start(self.start("mrow")))?;
+        self.write_row_children(node, font)?;
+        self.writer.write_event(Event::End(self.end("mrow")))?;
+        Ok(())
+    }
+
+    fn write_parent(&mut self, name: &str, children: &[&Node], font: Font) -> Result<()> {
+        self.writer.write_event(Event::Start(self.start(name)))?;
+        for child in children {
+            self.write_node(child, font)?;
+        }
+        self.writer.write_event(Event::End(self.end(name)))?;
+        Ok(())
+    }
+
+    fn write_fence(&mut self, fence: &str, form: &str) -> Result<()> {
+        let mut mo = self.start("mo");
+        // MathML Core ignores the fence flag, only form and stretchy affect rendering
+        if self.profile == Profile::MathMl3 {
+            mo.push_attribute(("fence", "true"));
+        }
+        mo.push_attribute(("form", form));
+        mo.push_attribute(("stretchy", "true"));
+        self.write_token(mo, fence)
+    }
+
+    // Writes a token whose font is italic by default when `italic` is set, upright otherwise
+    fn write_styled(&mut self, name: &str, text: &str, italic: bool, font: Font) -> Result<()> {
+        let variant = Variant::new(font.bold.unwrap_or(false), font.italic.unwrap_or(italic));
+        // Renderers only italicize single character identifiers on their own
+        let implicit = if name == "mi" && text.chars().count() == 1 {
+            Variant::Italic
+        } else {
+            Variant::Normal
+        };
+
+        let mut start = self.start(name);
+        if variant == implicit {
+            return self.write_token(start, text);
+        }
+        match self.profile {
+            Profile::MathMl3 => {
+                start.push_attribute(("mathvariant", variant.name()));
+                self.write_token(start, text)
+            }
+            // MathML Core only knows mathvariant="normal", other styles use styled characters
+            Profile::Core if variant == Variant::Normal => {
+                start.push_attribute(("mathvariant", "normal"));
+                self.write_token(start, text)
+            }
+            Profile::Core => self.write_token(start, &alphanumeric::styled(text, variant)),
+        }
+    }
+
+    fn write_token(&mut self, start: BytesStart, text: &str) -> Result<()> {
+        let end = start.to_end().into_owned();
+        self.writer.write_event(Event::Start(start))?;
+        self.writer.write_event(Event::Text(BytesText::new(text)))?;
+        self.writer.write_event(Event::End(end))?;
+        Ok(())
+    }
+}
+
+// Positions and precedences of the operators standing between two operands, leaving out
+// prefix operators like the sign in -a
+fn infix_operators(children: &[Node]) -> Vec<(usize, u8)> {
+    let mut infix = Vec::new();
+    for (index, child) in children.iter().enumerate() {
+        let Node::Operator {
+            kind, precedence, ..
+        } = child
+        else {
+            continue;
+        };
+        let after_operand = index > 0 && !matches!(children[index - 1], Node::Operator { .. });
+        if after_operand && *kind != Some(OperatorKind::Unary) {
+            infix.push((index, *precedence));
+        }
+    }
+    infix
+}
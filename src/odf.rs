@@ -0,0 +1,55 @@
+use anyhow::Result;
+use quick_xml::Reader;
+use quick_xml::escape::resolve_predefined_entity;
+use quick_xml::events::{BytesStart, Event};
+
+mod flat;
+#[cfg(feature = "odf")]
+mod package;
+
+pub use flat::{FlatFormula, read_flat_formulas, read_flat_formulas_from_str};
+#[cfg(feature = "odf")]
+pub use package::{
+    Formula, convert_formulas, read_formulas, read_formulas_from, rewrite_formulas,
+    rewrite_formulas_in_place, rewrite_formulas_to,
+};
+
+// Value of the attribute with the given local name, whatever its namespace prefix
+pub(crate) fn attribute(start: &BytesStart, name: &[u8]) -> Result<Option<String>> {
+    for attribute in start.attributes() {
+        let attribute = attribute?;
+        if attribute.key.local_name().as_ref() == name {
+            return Ok(Some(attribute.unescape_value()?.into_owned()));
+        }
+    }
+    Ok(None)
+}
+
+pub(crate) fn is_starmath_annotation(start: &BytesStart) -> Result<bool> {
+    Ok(start.local_name().as_ref() == b"annotation"
+        && attribute(start, b"encoding")?.as_deref() == Some("StarMath 5.0"))
+}
+
+// Text up to the end of the current element, with entity references resolved
+pub(crate) fn read_text(reader: &mut Reader<&[u8]>) -> Result<String> {
+    let mut text = String::new();
+    loop {
+        match reader.read_event()? {
+            Event::Text(content) => text.push_str(&content.xml_content()?),
+            Event::CData(content) => text.push_str(&content.decode()?),
+            Event::GeneralRef(reference) => {
+                if let Some(ch) = reference.resolve_char_ref()? {
+                    text.push(ch);
+                } else {
+                    let name = reference.decode()?;
+                    match resolve_predefined_entity(&name) {
+                        Some(value) => text.push_str(value),
+                        None => anyhow::bail!("Unknown entity &{};", name),
+                    }
+                }
+            }
+            Event::End(_) | Event::Eof => return Ok(text),
+            _ => {}
+        }
+    }
+}
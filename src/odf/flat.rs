@@ -0,0 +1,62 @@
+use anyhow::{Context, Result};
+use quick_xml::Reader;
+use quick_xml::events::Event;
+use std::fs;
+use std::path::Path;
+
+use super::{attribute, is_starmath_annotation, read_text};
+use crate::{Options, starmath_to_mathml_with_options};
+
+/// A formula found inline in a flat ODF document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlatFormula {
+    /// Name of the enclosing `draw:frame`, absent for a flat formula document.
+    pub frame: Option<String>,
+    pub starmath: String,
+}
+
+impl FlatFormula {
+    pub fn to_mathml(&self, options: &Options) -> Result<String> {
+        starmath_to_mathml_with_options(&self.starmath, options)
+    }
+}
+
+/// Lists the formulas of a flat `.fodt`, `.fodf` or other flat ODF document.
+pub fn read_flat_formulas(path: impl AsRef<Path>) -> Result<Vec<FlatFormula>> {
+    let path = path.as_ref();
+    let content =
+        fs::read_to_string(path).with_context(|| format!("Cannot read {}", path.display()))?;
+    read_flat_formulas_from_str(&content)
+}
+
+pub fn read_flat_formulas_from_str(content: &str) -> Result<Vec<FlatFormula>> {
+    let mut reader = Reader::from_str(content);
+    // One entry per open element, holding the frame name for draw:frame elements
+    let mut open: Vec<Option<String>> = Vec::new();
+    let mut formulas = Vec::new();
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(start) if is_starmath_annotation(&start)? => {
+                let frame = open.iter().rev().find_map(Clone::clone);
+                let starmath = read_text(&mut reader)?;
+                formulas.push(FlatFormula { frame, starmath });
+            }
+            Event::Start(start) => {
+                let frame = if start.local_name().as_ref() == b"frame" {
+                    attribute(&start, b"name")?
+                } else {
+                    None
+                };
+                open.push(frame);
+            }
+            Event::End(_) => {
+                open.pop();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(formulas)
+}
@@ -0,0 +1,203 @@
+use anyhow::{Context, Result};
+use quick_xml::Reader;
+use quick_xml::events::Event;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{Read, Seek, Write};
+use std::path::Path;
+use zip::write::SimpleFileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+use super::{attribute, is_starmath_annotation, read_text};
+use crate::{Options, starmath_to_mathml_with_options};
+
+const FORMULA_MEDIA_TYPE: &str = "application/vnd.oasis.opendocument.formula";
+
+/// A formula object stored in an ODF package.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Formula {
+    /// Path of the object's content inside the package, like `Object 1/content.xml`.
+    pub path: String,
+    pub starmath: String,
+}
+
+impl Formula {
+    pub fn to_mathml(&self, options: &Options) -> Result<String> {
+        starmath_to_mathml_with_options(&self.starmath, options)
+    }
+}
+
+/// Lists the formula objects of an `.odt`, `.odf` or other ODF package.
+pub fn read_formulas(path: impl AsRef<Path>) -> Result<Vec<Formula>> {
+    let path = path.as_ref();
+    let file = File::open(path).with_context(|| format!("Cannot open {}", path.display()))?;
+    read_formulas_from(file)
+}
+
+pub fn read_formulas_from<R: Read + Seek>(reader: R) -> Result<Vec<Formula>> {
+    let mut archive = ZipArchive::new(reader)?;
+    let mut formulas = Vec::new();
+
+    for path in formula_paths(&mut archive)? {
+        let content = read_entry(&mut archive, &path)?;
+        // Objects saved without their source only carry MathML, nothing to convert back
+        if let Some(starmath) = find_annotation(&content)? {
+            formulas.push(Formula { path, starmath });
+        }
+    }
+
+    Ok(formulas)
+}
+
+/// Converts every formula of a package, pairing each object path with its MathML.
+pub fn convert_formulas(
+    path: impl AsRef<Path>,
+    options: &Options,
+) -> Result<Vec<(String, String)>> {
+    read_formulas(path)?
+        .into_iter()
+        .map(|formula| {
+            let mathml = formula
+                .to_mathml(options)
+                .with_context(|| format!("Cannot convert {}", formula.path))?;
+            Ok((formula.path, mathml))
+        })
+        .collect()
+}
+
+/// Regenerates the MathML of every formula object of `input` from its StarMath annotation and
+/// writes the package to `output`, returning how many formulas were rewritten.
+///
+/// All other entries, the manifest included, are copied byte for byte. Elements get the `math`
+/// prefix unless `options` sets another one.
+pub fn rewrite_formulas(
+    input: impl AsRef<Path>,
+    output: impl AsRef<Path>,
+    options: &Options,
+) -> Result<usize> {
+    let (input, output) = (input.as_ref(), output.as_ref());
+    // Creating the output would truncate the input before it is read
+    if let (Ok(input), Ok(output)) = (fs::canonicalize(input), fs::canonicalize(output))
+        && input == output
+    {
+        return rewrite_formulas_in_place(input, options);
+    }
+    let reader = File::open(input).with_context(|| format!("Cannot open {}", input.display()))?;
+    let writer =
+        File::create(output).with_context(|| format!("Cannot create {}", output.display()))?;
+    rewrite_formulas_to(reader, writer, options)
+}
+
+pub fn rewrite_formulas_in_place(path: impl AsRef<Path>, options: &Options) -> Result<usize> {
+    let path = path.as_ref();
+    let file_name = path
+        .file_name()
+        .with_context(|| format!("{} is not a file", path.display()))?;
+    // Written next to the original so the final rename stays on the same file system
+    let temporary = path.with_file_name(format!(".{}.sm2mml", file_name.to_string_lossy()));
+
+    let count = match rewrite_formulas(path, &temporary, options) {
+        Ok(count) => count,
+        Err(error) => {
+            let _ = fs::remove_file(&temporary);
+            return Err(error);
+        }
+    };
+    fs::rename(&temporary, path)?;
+    Ok(count)
+}
+
+pub fn rewrite_formulas_to<R: Read + Seek, W: Write + Seek>(
+    reader: R,
+    writer: W,
+    options: &Options,
+) -> Result<usize> {
+    let mut archive = ZipArchive::new(reader)?;
+    let mut options = options.clone();
+    options.prefix.get_or_insert_with(|| "math".to_string());
+
+    // New content of each formula object, by entry name
+    let mut contents = HashMap::new();
+    for path in formula_paths(&mut archive)? {
+        let content = read_entry(&mut archive, &path)?;
+        if let Some(starmath) = find_annotation(&content)? {
+            let mathml = starmath_to_mathml_with_options(&starmath, &options)
+                .with_context(|| format!("Cannot convert {}", path))?;
+            contents.insert(path, mathml);
+        }
+    }
+
+    let mut package = ZipWriter::new(writer);
+    for i in 0..archive.len() {
+        let entry = archive.by_index_raw(i)?;
+        match contents.get(entry.name()) {
+            Some(mathml) => {
+                let mut file_options =
+                    SimpleFileOptions::default().compression_method(entry.compression());
+                if let Some(time) = entry.last_modified() {
+                    file_options = file_options.last_modified_time(time);
+                }
+                let name = entry.name().to_string();
+                drop(entry);
+                package.start_file(name, file_options)?;
+                package.write_all(mathml.as_bytes())?;
+            }
+            None => package.raw_copy_file(entry)?,
+        }
+    }
+    package.finish()?;
+
+    Ok(contents.len())
+}
+
+fn read_entry<R: Read + Seek>(archive: &mut ZipArchive<R>, name: &str) -> Result<String> {
+    let mut entry = archive
+        .by_name(name)
+        .with_context(|| format!("Missing {} in package", name))?;
+    let mut content = String::new();
+    entry.read_to_string(&mut content)?;
+    Ok(content)
+}
+
+// Content files of the formula objects listed in the package manifest
+fn formula_paths<R: Read + Seek>(archive: &mut ZipArchive<R>) -> Result<Vec<String>> {
+    let manifest = read_entry(archive, "META-INF/manifest.xml")?;
+    let mut reader = Reader::from_str(&manifest);
+    let mut paths = Vec::new();
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(entry) | Event::Empty(entry)
+                if entry.local_name().as_ref() == b"file-entry" =>
+            {
+                let media_type = attribute(&entry, b"media-type")?;
+                let full_path = attribute(&entry, b"full-path")?;
+                if let (Some(FORMULA_MEDIA_TYPE), Some(full_path)) =
+                    (media_type.as_deref(), full_path)
+                {
+                    // A formula document lists itself as "/", embedded objects as "Object 1/"
+                    let directory = full_path.trim_start_matches('/');
+                    paths.push(format!("{}content.xml", directory));
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(paths)
+}
+
+// StarMath source stored in the annotation of a formula's MathML
+fn find_annotation(content: &str) -> Result<Option<String>> {
+    let mut reader = Reader::from_str(content);
+    loop {
+        match reader.read_event()? {
+            Event::Start(start) if is_starmath_annotation(&start)? => {
+                return read_text(&mut reader).map(Some);
+            }
+            Event::Eof => return Ok(None),
+            _ => {}
+        }
+    }
+}
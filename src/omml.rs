@@ -0,0 +1,233 @@
+use anyhow::Result;
+use quick_xml::Writer;
+use quick_xml::events::{BytesEnd, BytesStart, BytesText, Event};
+use std::io::Cursor;
+
+use crate::alphanumeric::Variant;
+use crate::ast::{Font, Node, is_integral};
+use crate::{Options, parser};
+
+const NAMESPACE: &str = "http://schemas.openxmlformats.org/officeDocument/2006/math";
+
+type XmlWriter = Writer<Cursor<Vec<u8>>>;
+
+/// Converts StarMath to an Office MathML `m:oMath` element, as found in `.docx` documents.
+pub fn starmath_to_omml(starmath: &str) -> Result<String> {
+    starmath_to_omml_with_options(starmath, &Options::default())
+}
+
+pub fn starmath_to_omml_with_options(starmath: &str, options: &Options) -> Result<String> {
+    let root = parser::parse(starmath, options)?;
+
+    let mut writer = Writer::new_with_indent(Cursor::new(Vec::new()), b' ', 2);
+    let mut math = BytesStart::new("m:oMath");
+    math.push_attribute(("xmlns:m", NAMESPACE));
+    writer.write_event(Event::Start(math))?;
+    OmmlWriter {
+        writer: &mut writer,
+    }
+    .write_children(&root, Font::default())?;
+    writer.write_event(Event::End(BytesEnd::new("m:oMath")))?;
+
+    Ok(String::from_utf8(writer.into_inner().into_inner())?)
+}
+
+struct OmmlWriter<'a> {
+    writer: &'a mut XmlWriter,
+}
+
+impl OmmlWriter<'_> {
+    // OMML has no row element, sequences are written inline into their argument
+    fn write_children(&mut self, node: &Node, font: Font) -> Result<()> {
+        match node {
+            Node::Row(children) => {
+                for child in children {
+                    self.write_node(child, font)?;
+                }
+                Ok(())
+            }
+            _ => self.write_node(node, font),
+        }
+    }
+
+    fn write_node(&mut self, node: &Node, font: Font) -> Result<()> {
+        match node {
+            Node::Number(number) => self.write_run(number, false, font),
+            Node::Identifier(name) => self.write_run(name, true, font),
+            Node::Function(name) => self.write_run(name, false, font),
+            Node::Operator { symbol, .. } => self.write_run(symbol, false, Font::default()),
+            Node::Text(text) => {
+                // m:nor and m:sty are alternatives, normal text takes its style from the run
+                self.start("m:r")?;
+                self.start("m:rPr")?;
+                self.empty("m:nor")?;
+                self.end("m:rPr")?;
+                self.write_text(text)?;
+                self.end("m:r")
+            }
+            Node::Row(_) => self.write_children(node, font),
+            Node::Sub(base, sub) => {
+                self.start("m:sSub")?;
+                self.argument("m:e", base, font)?;
+                self.argument("m:sub", sub, font)?;
+                self.end("m:sSub")
+            }
+            Node::Sup(base, sup) => {
+                self.start("m:sSup")?;
+                self.argument("m:e", base, font)?;
+                self.argument("m:sup", sup, font)?;
+                self.end("m:sSup")
+            }
+            Node::Frac(num, den) => {
+                self.start("m:f")?;
+                self.argument("m:num", num, font)?;
+                self.argument("m:den", den, font)?;
+                self.end("m:f")
+            }
+            Node::Sqrt(body) => {
+                self.start("m:rad")?;
+                self.start("m:radPr")?;
+                self.property("m:degHide", "1")?;
+                self.end("m:radPr")?;
+                self.empty("m:deg")?;
+                self.argument("m:e", body, font)?;
+                self.end("m:rad")
+            }
+            Node::Root(index, body) => {
+                self.start("m:rad")?;
+                self.argument("m:deg", index, font)?;
+                self.argument("m:e", body, font)?;
+                self.end("m:rad")
+            }
+            Node::Accent(base, accent) => {
+                self.start("m:acc")?;
+                self.start("m:accPr")?;
+                self.property("m:chr", accent)?;
+                self.end("m:accPr")?;
+                self.argument("m:e", base, font)?;
+                self.end("m:acc")
+            }
+            Node::Fenced { open, body, close } => {
+                self.start("m:d")?;
+                self.start("m:dPr")?;
+                self.property("m:begChr", open)?;
+                // An empty character leaves the delimiter out
+                self.property("m:endChr", close.as_deref().unwrap_or(""))?;
+                self.end("m:dPr")?;
+                self.argument("m:e", body, font)?;
+                self.end("m:d")
+            }
+            Node::Styled(style, body) => self.write_node(body, font.with(*style)),
+            Node::LargeOp {
+                symbol,
+                from,
+                to,
+                body,
+            } => {
+                self.start("m:nary")?;
+                self.start("m:naryPr")?;
+                self.property("m:chr", symbol)?;
+                let location = if is_integral(symbol) {
+                    "subSup"
+                } else {
+                    "undOvr"
+                };
+                self.property("m:limLoc", location)?;
+                if from.is_none() {
+                    self.property("m:subHide", "1")?;
+                }
+                if to.is_none() {
+                    self.property("m:supHide", "1")?;
+                }
+                self.end("m:naryPr")?;
+                let empty = Node::empty();
+                self.argument("m:sub", from.as_deref().unwrap_or(&empty), font)?;
+                self.argument("m:sup", to.as_deref().unwrap_or(&empty), font)?;
+                self.argument("m:e", body.as_deref().unwrap_or(&empty), font)?;
+                self.end("m:nary")
+            }
+            Node::Matrix(rows) => {
+                self.start("m:m")?;
+                for row in rows {
+                    self.start("m:mr")?;
+                    for cell in row {
+                        self.argument("m:e", cell, font)?;
+                    }
+                    self.end("m:mr")?;
+                }
+                self.end("m:m")
+            }
+        }
+    }
+
+    // Writes a run whose font is italic by default when `italic` is set, upright otherwise
+    fn write_run(&mut self, text: &str, italic: bool, font: Font) -> Result<()> {
+        // Word italicizes letters and leaves everything else upright on its own
+        let letters = text.chars().any(char::is_alphabetic);
+        let variant = Variant::new(
+            font.bold.unwrap_or(false),
+            font.italic.unwrap_or(italic && letters),
+        );
+        let implicit = if letters {
+            Variant::Italic
+        } else {
+            Variant::Normal
+        };
+
+        self.start("m:r")?;
+        if variant != implicit {
+            let style = match variant {
+                Variant::Normal => "p",
+                Variant::Italic => "i",
+                Variant::Bold => "b",
+                Variant::BoldItalic => "bi",
+            };
+            self.start("m:rPr")?;
+            self.property("m:sty", style)?;
+            self.end("m:rPr")?;
+        }
+        self.write_text(text)?;
+        self.end("m:r")
+    }
+
+    fn write_text(&mut self, text: &str) -> Result<()> {
+        let mut t = BytesStart::new("m:t");
+        // Word trims runs otherwise
+        if text.starts_with(' ') || text.ends_with(' ') {
+            t.push_attribute(("xml:space", "preserve"));
+        }
+        self.writer.write_event(Event::Start(t))?;
+        self.writer.write_event(Event::Text(BytesText::new(text)))?;
+        self.end("m:t")
+    }
+
+    fn argument(&mut self, name: &str, node: &Node, font: Font) -> Result<()> {
+        self.start(name)?;
+        self.write_children(node, font)?;
+        self.end(name)
+    }
+
+    fn property(&mut self, name: &str, value: &str) -> Result<()> {
+        let mut property = BytesStart::new(name);
+        property.push_attribute(("m:val", value));
+        self.writer.write_event(Event::Empty(property))?;
+        Ok(())
+    }
+
+    fn start(&mut self, name: &str) -> Result<()> {
+        self.writer
+            .write_event(Event::Start(BytesStart::new(name)))?;
+        Ok(())
+    }
+
+    fn empty(&mut self, name: &str) -> Result<()> {
+        self.writer
+            .write_event(Event::Empty(BytesStart::new(name)))?;
+        Ok(())
+    }
+
+    fn end(&mut self, name: &str) -> Result<()> {
+        self.writer.write_event(Event::End(BytesEnd::new(name)))?;
+        Ok(())
+    }
+}
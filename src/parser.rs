@@ -0,0 +1,733 @@
+use anyhow::Result;
+use std::ops::Range;
+
+use crate::ast::{FontStyle, Node};
+use crate::symbols::greek_letter;
+use crate::{INVISIBLE_TIMES, IdentifierPolicy, Options, Registry};
+
+// Guards against macros that expand to themselves
+const MAX_MACRO_EXPANSIONS: usize = 1024;
+
+pub(crate) fn parse(input: &str, options: &Options) -> Result<Node> {
+    let tokens = expand_macros(tokenize(input), &options.registry)?;
+    let mut parser = Parser::new(tokens, options);
+    let root = parser.parse_expression()?;
+    // The top-level expression only stops early at a closing brace nothing opened
+    if parser.peek().is_some() {
+        anyhow::bail!("Unmatched closing brace");
+    }
+    Ok(root)
+}
+
+// Words with a meaning of their own, matched case-insensitively by the formatter
+pub(crate) const KEYWORDS: &[&str] = &[
+    "acute", "sqrt", "nroot", "sum", "prod", "coprod", "int", "iint", "iiint", "lint", "from",
+    "to", "matrix", "left", "right", "bold", "nbold", "ital", "italic", "nitalic", "times", "rsub",
+    "over", "lbrace", "rbrace", "langle", "rangle", "lline", "rline", "ldline", "rdline", "none",
+];
+
+// Characters that make up operators, split from the letters and digits around them
+const OPERATOR_CHARS: &[char] = &['+', '-', '*', '/', '=', '<', '>', '±', '−', '×', '·', '÷'];
+
+// Characters that end a word
+const SEPARATORS: &[char] = &[' ', '{', '}', '(', ')', '"', '\t', '\n', '^'];
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Token {
+    Word(String),
+    LBrace,
+    RBrace,
+    LParen,
+    RParen,
+    String(String),
+}
+
+pub(crate) fn tokenize(input: &str) -> Vec<Token> {
+    tokenize_spanned(input)
+        .into_iter()
+        .map(|(token, _)| token)
+        .collect()
+}
+
+// Tokens along with the byte range they cover in the input
+pub(crate) fn tokenize_spanned(input: &str) -> Vec<(Token, Range<usize>)> {
+    // Decode HTML entities first
+    let decoded = decode_html_entities(input);
+
+    let mut tokens = Vec::new();
+    let mut chars = decoded.into_iter().peekable();
+
+    while let Some(&(ch, ref span)) = chars.peek() {
+        let start = span.start;
+        let single = match ch {
+            '{' => Some(Token::LBrace),
+            '}' => Some(Token::RBrace),
+            '(' => Some(Token::LParen),
+            ')' => Some(Token::RParen),
+            _ => None,
+        };
+        if let Some(token) = single {
+            let (_, span) = chars.next().unwrap();
+            tokens.push((token, span));
+            continue;
+        }
+        match ch {
+            ' ' | '\t' | '\n' => {
+                chars.next();
+            }
+            '"' => {
+                let (_, mut span) = chars.next().unwrap();
+                let mut string = String::new();
+                for (ch, char_span) in chars.by_ref() {
+                    span.end = char_span.end;
+                    if ch == '"' {
+                        break;
+                    }
+                    string.push(ch);
+                }
+                tokens.push((Token::String(string), start..span.end));
+            }
+            // Superscripts bind to what comes before, as in x^2
+            '^' => {
+                let (_, span) = chars.next().unwrap();
+                tokens.push((Token::Word("^".to_string()), span));
+            }
+            _ => {
+                // A run of operator characters is a word of its own, so a+b reads as a + b
+                let operator = OPERATOR_CHARS.contains(&ch);
+                let mut word = String::new();
+                let mut end = start;
+                while let Some((ch, span)) = chars.next_if(|(ch, _)| {
+                    !SEPARATORS.contains(ch) && OPERATOR_CHARS.contains(ch) == operator
+                }) {
+                    word.push(ch);
+                    end = span.end;
+                }
+                tokens.push((Token::Word(word), start..end));
+            }
+        }
+    }
+
+    tokens
+}
+
+fn expand_macros(tokens: Vec<Token>, registry: &Registry) -> Result<Vec<Token>> {
+    let mut expanded = Vec::with_capacity(tokens.len());
+    let mut pending: Vec<Token> = tokens.into_iter().rev().collect();
+    let mut expansions = 0;
+
+    while let Some(token) = pending.pop() {
+        let expansion = match &token {
+            Token::Word(word) => registry.macro_expansion(word),
+            _ => None,
+        };
+        match expansion {
+            Some(expansion) => {
+                expansions += 1;
+                if expansions > MAX_MACRO_EXPANSIONS {
+                    anyhow::bail!("Too many macro expansions, check for recursive macros");
+                }
+                // Expanded tokens go back on the stack so nested macros are expanded too
+                pending.extend(tokenize(expansion).into_iter().rev());
+            }
+            None => expanded.push(token),
+        }
+    }
+
+    Ok(expanded)
+}
+
+// Glyph of a fence given after left or right, named or written out
+fn fence(word: &str) -> String {
+    let glyph = match word {
+        "lbrace" => "{",
+        "rbrace" => "}",
+        "langle" => "⟨",
+        "rangle" => "⟩",
+        "lline" | "rline" => "|",
+        "ldline" | "rdline" => "‖",
+        "none" => "",
+        _ => word,
+    };
+    glyph.to_string()
+}
+
+// Characters of the input with their byte ranges, entities decoded to a single character
+fn decode_html_entities(input: &str) -> Vec<(char, Range<usize>)> {
+    const ENTITIES: &[(&str, char)] = &[
+        ("&amp;", '&'),
+        ("&quot;", '"'),
+        ("&lt;", '<'),
+        ("&gt;", '>'),
+        ("“", '"'),
+        ("”", '"'),
+    ];
+
+    let mut decoded = Vec::new();
+    let mut pos = 0;
+    while let Some(ch) = input[pos..].chars().next() {
+        let rest = &input[pos..];
+        let (ch, len) = ENTITIES
+            .iter()
+            .find(|(entity, _)| rest.starts_with(entity))
+            .map_or((ch, ch.len_utf8()), |(entity, decoded)| {
+                (*decoded, entity.len())
+            });
+        decoded.push((ch, pos..pos + len));
+        pos += len;
+    }
+    decoded
+}
+
+// Builds a script node from its base and its operand
+type ScriptBuilder = fn(Box<Node>, Box<Node>) -> Node;
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    options: &'a Options,
+}
+
+impl<'a> Parser<'a> {
+    fn new(tokens: Vec<Token>, options: &'a Options) -> Self {
+        Parser {
+            tokens,
+            pos: 0,
+            options,
+        }
+    }
+
+    fn sub_parser(&self, range: Range<usize>) -> Parser<'a> {
+        Parser::new(self.tokens[range].to_vec(), self.options)
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn advance(&mut self) -> Option<&Token> {
+        let token = self.tokens.get(self.pos);
+        if token.is_some() {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn parse_expression(&mut self) -> Result<Node> {
+        let children = self.parse_sequence(|token| matches!(token, Token::RBrace))?;
+        Ok(Node::Row(children))
+    }
+
+    fn parse_sequence(&mut self, is_end: impl Fn(&Token) -> bool) -> Result<Vec<Node>> {
+        let mut children = Vec::new();
+        let mut after_operand = false;
+        while let Some(token) = self.peek() {
+            if is_end(token) {
+                break;
+            }
+
+            let operand = self.starts_operand();
+            if self.options.invisible_times && after_operand && operand {
+                children.push(Node::operator(INVISIBLE_TIMES));
+            }
+            // A function name is applied to what follows rather than multiplied with it
+            after_operand =
+                operand && !matches!(token, Token::Word(w) if self.options.registry.is_function(w));
+
+            if let Some(node) = self.parse_element()? {
+                children.push(node);
+            }
+
+            // over divides the whole element before it, so sqrt x over 2 halves the root
+            while matches!(self.peek(), Some(Token::Word(w)) if w == "over")
+                && let Some(numerator) = children.pop()
+            {
+                self.advance(); // skip "over"
+                let denominator = self.parse_operand()?;
+                children.push(Node::Frac(Box::new(numerator), Box::new(denominator)));
+            }
+        }
+        Ok(children)
+    }
+
+    // Whether the next element is something that can be multiplied, as opposed to an operator
+    fn starts_operand(&self) -> bool {
+        match self.peek() {
+            Some(Token::Word(word)) => {
+                if self.options.registry.operator(word).is_some() {
+                    return false;
+                }
+                match word.as_str() {
+                    "right" | "times" => false,
+                    // Symbols such as "+" or "<" fall through as identifiers
+                    _ => word.chars().any(char::is_alphanumeric),
+                }
+            }
+            Some(Token::String(_)) | Some(Token::LBrace) => true,
+            Some(Token::RBrace) | Some(Token::LParen) | Some(Token::RParen) | None => false,
+        }
+    }
+
+    fn parse_element(&mut self) -> Result<Option<Node>> {
+        let token = match self.peek() {
+            Some(t) => t.clone(),
+            None => return Ok(None),
+        };
+
+        if let Token::Word(ref word) = token
+            && let Some(operator) = self.options.registry.operator(word)
+        {
+            self.advance();
+            return Ok(Some(Node::Operator {
+                symbol: operator.glyph.clone(),
+                kind: Some(operator.kind),
+                precedence: operator.precedence,
+            }));
+        }
+
+        let node = match token {
+            Token::Word(ref word) => match word.as_str() {
+                "acute" => self.parse_accent("´")?,
+                "sqrt" => self.parse_sqrt()?,
+                "nroot" => self.parse_root()?,
+                "sum" => self.parse_large_operator("∑")?,
+                "prod" => self.parse_large_operator("∏")?,
+                "coprod" => self.parse_large_operator("∐")?,
+                "int" => self.parse_large_operator("∫")?,
+                "iint" => self.parse_large_operator("∬")?,
+                "iiint" => self.parse_large_operator("∭")?,
+                "lint" => self.parse_large_operator("∮")?,
+                "matrix" => self.parse_matrix()?,
+                "left" => return self.parse_left_fence(),
+                "bold" => self.parse_styled(FontStyle::Bold)?,
+                "nbold" => self.parse_styled(FontStyle::NotBold)?,
+                "ital" | "italic" => self.parse_styled(FontStyle::Italic)?,
+                "nitalic" => self.parse_styled(FontStyle::NotItalic)?,
+                "right" => {
+                    self.advance();
+                    // Skip the closing parenthesis
+                    self.advance();
+                    return Ok(None);
+                }
+                "%" => {
+                    self.advance();
+                    Node::Text("%".to_string())
+                }
+                "=" => {
+                    self.advance();
+                    Node::operator("=")
+                }
+                "±" | "+-" => {
+                    self.advance();
+                    Node::operator("±")
+                }
+                "−" | "-" => {
+                    self.advance();
+                    Node::operator("−")
+                }
+                "×" | "*" | "times" => {
+                    self.advance();
+                    Node::operator("×")
+                }
+                _ => {
+                    self.advance();
+                    let base = self.token_node(word);
+
+                    // Check if rsub or ^ follows this token
+                    match self.peek() {
+                        Some(Token::Word(op)) if op == "rsub" => {
+                            self.advance(); // skip "rsub"
+                            Node::Sub(Box::new(base), Box::new(self.parse_operand()?))
+                        }
+                        Some(Token::Word(op)) if op == "^" => {
+                            self.advance(); // skip "^"
+                            Node::Sup(Box::new(base), Box::new(self.parse_operand()?))
+                        }
+                        // Normal identifier or number
+                        _ => base,
+                    }
+                }
+            },
+            Token::String(ref s) => {
+                self.advance();
+                Node::Text(s.clone())
+            }
+            Token::LBrace => {
+                self.advance();
+                self.parse_group()?
+            }
+            Token::RBrace => {
+                return Ok(None);
+            }
+            Token::LParen | Token::RParen => {
+                // Parentheses are handled by parse_left_fence
+                // Skip them if encountered here
+                self.advance();
+                return Ok(None);
+            }
+        };
+        Ok(Some(node))
+    }
+
+    // Operand of a script, fraction, root or accent, which must always be present
+    fn parse_operand(&mut self) -> Result<Node> {
+        Ok(self.parse_element()?.unwrap_or_else(Node::empty))
+    }
+
+    fn parse_group(&mut self) -> Result<Node> {
+        // Look ahead to see what follows this group
+        let group_start = self.pos;
+        let mut brace_count = 1;
+        let mut temp_pos = self.pos;
+
+        while temp_pos < self.tokens.len() && brace_count > 0 {
+            match &self.tokens[temp_pos] {
+                Token::LBrace => brace_count += 1,
+                Token::RBrace => brace_count -= 1,
+                _ => {}
+            }
+            temp_pos += 1;
+        }
+
+        let group_end = temp_pos;
+        // Without a closing brace the group runs to the end of the input
+        let content_end = if brace_count == 0 {
+            group_end - 1
+        } else {
+            group_end
+        };
+
+        // First, check if there's an "over" operator INSIDE this group (at brace level 0)
+        let mut over_pos = None;
+        let mut inner_brace_count = 0;
+        for i in group_start..content_end {
+            match &self.tokens[i] {
+                Token::LBrace => inner_brace_count += 1,
+                Token::RBrace => inner_brace_count -= 1,
+                Token::Word(w) if w == "over" && inner_brace_count == 0 => {
+                    over_pos = Some(i);
+                    break;
+                }
+                _ => {}
+            }
+        }
+
+        // If there's an "over" inside the group, create a fraction
+        if let Some(over_idx) = over_pos {
+            // Numerator (tokens before "over") and denominator (tokens after "over")
+            let numerator = self.sub_parser(group_start..over_idx).parse_expression()?;
+            let denominator = self
+                .sub_parser(over_idx + 1..content_end)
+                .parse_expression()?;
+
+            self.pos = group_end;
+            return Ok(Node::Frac(Box::new(numerator), Box::new(denominator)));
+        }
+
+        let group = self
+            .sub_parser(group_start..content_end)
+            .parse_expression()?;
+
+        // Check what follows
+        let script: Option<ScriptBuilder> = match self.tokens.get(group_end) {
+            Some(Token::Word(op)) if op == "rsub" => Some(Node::Sub),
+            Some(Token::Word(op)) if op == "^" => Some(Node::Sup),
+            _ => None,
+        };
+
+        if let Some(script) = script {
+            self.pos = group_end + 1; // Skip past rsub or ^
+            let operand = self.parse_operand()?;
+
+            // Skip the closing brace of the script if it exists
+            if matches!(self.peek(), Some(Token::RBrace)) {
+                self.advance();
+            }
+
+            return Ok(script(Box::new(group), Box::new(operand)));
+        }
+
+        // Regular group
+        self.pos = group_end;
+        Ok(group)
+    }
+
+    fn parse_accent(&mut self, accent: &str) -> Result<Node> {
+        self.advance(); // skip "acute"
+        let base = self.parse_operand()?;
+        Ok(Node::Accent(Box::new(base), accent.to_string()))
+    }
+
+    fn parse_styled(&mut self, style: FontStyle) -> Result<Node> {
+        self.advance(); // skip the style keyword
+        Ok(Node::Styled(style, Box::new(self.parse_operand()?)))
+    }
+
+    fn parse_sqrt(&mut self) -> Result<Node> {
+        self.advance(); // skip "sqrt"
+        Ok(Node::Sqrt(Box::new(self.parse_operand()?)))
+    }
+
+    fn parse_root(&mut self) -> Result<Node> {
+        self.advance(); // skip "nroot"
+        let index = self.parse_operand()?;
+        let radicand = self.parse_operand()?;
+        Ok(Node::Root(Box::new(index), Box::new(radicand)))
+    }
+
+    fn parse_large_operator(&mut self, symbol: &str) -> Result<Node> {
+        self.advance(); // skip "sum", "int", ...
+
+        let from = self.parse_limit("from")?;
+        let to = self.parse_limit("to")?;
+        let body = self.parse_element()?;
+
+        Ok(Node::LargeOp {
+            symbol: symbol.to_string(),
+            from,
+            to,
+            body: body.map(Box::new),
+        })
+    }
+
+    fn parse_limit(&mut self, keyword: &str) -> Result<Option<Box<Node>>> {
+        match self.peek() {
+            Some(Token::Word(w)) if w == keyword => {
+                self.advance();
+                Ok(Some(Box::new(self.parse_operand()?)))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    // matrix{ a # b ## c # d }, "#" separating cells and "##" rows
+    fn parse_matrix(&mut self) -> Result<Node> {
+        self.advance(); // skip "matrix"
+        if !matches!(self.peek(), Some(Token::LBrace)) {
+            return Ok(Node::Matrix(Vec::new()));
+        }
+        self.advance();
+
+        let mut rows = Vec::new();
+        let mut row = Vec::new();
+        loop {
+            let cell = self.parse_sequence(|token| match token {
+                Token::RBrace => true,
+                Token::Word(w) => w == "#" || w == "##",
+                _ => false,
+            })?;
+            row.push(Node::Row(cell));
+
+            match self.advance() {
+                Some(Token::Word(w)) if w == "#" => {}
+                Some(Token::Word(w)) if w == "##" => rows.push(std::mem::take(&mut row)),
+                // Closing brace or end of input
+                _ => break,
+            }
+        }
+        rows.push(row);
+
+        Ok(Node::Matrix(rows))
+    }
+
+    fn parse_left_fence(&mut self) -> Result<Option<Node>> {
+        self.advance(); // skip "left"
+
+        // Get the opening fence
+        let open = match self.peek() {
+            Some(Token::Word(f)) => {
+                let s = fence(f);
+                self.advance();
+                s
+            }
+            Some(Token::LParen) => {
+                self.advance();
+                "(".to_string()
+            }
+            _ => return Ok(None),
+        };
+
+        // Parse until we hit "right", or the brace closing the enclosing group
+        let body = self.parse_sequence(|token| match token {
+            Token::Word(w) => w == "right",
+            Token::RBrace => true,
+            _ => false,
+        })?;
+        if matches!(self.peek(), Some(Token::RBrace)) {
+            return Ok(Some(Node::Fenced {
+                open,
+                body: Box::new(Node::Row(body)),
+                close: None,
+            }));
+        }
+
+        // Closing fence
+        self.advance(); // skip "right"
+        let close = match self.peek() {
+            Some(Token::Word(f)) => {
+                let s = fence(f);
+                self.advance();
+                Some(s)
+            }
+            Some(Token::RParen) => {
+                self.advance();
+                Some(")".to_string())
+            }
+            _ => None,
+        };
+
+        Ok(Some(Node::Fenced {
+            open,
+            body: Box::new(Node::Row(body)),
+            close,
+        }))
+    }
+
+    fn token_node(&self, word: &str) -> Node {
+        // Determine if this is a number or identifier
+        let is_number = word
+            .chars()
+            .all(|c| c.is_ascii_digit() || c == ',' || c == '.');
+        if is_number {
+            return Node::Number(word.to_string());
+        }
+
+        if let Some(name) = word.strip_prefix('%')
+            && let Some(letter) = greek_letter(name)
+        {
+            return Node::Identifier(letter.to_string());
+        }
+
+        if word.chars().all(|c| OPERATOR_CHARS.contains(&c)) {
+            return Node::operator(word);
+        }
+
+        // Check if this is a standard mathematical function (should be upright)
+        if self.options.registry.is_function(word) {
+            return Node::Function(word.to_string());
+        }
+
+        if self.options.identifiers == IdentifierPolicy::Letters
+            && word.chars().count() > 1
+            && word.chars().all(char::is_alphabetic)
+        {
+            // One identifier per letter, grouped so the word stays a single operand
+            let mut letters = Vec::new();
+            for (i, letter) in word.chars().enumerate() {
+                if self.options.invisible_times && i > 0 {
+                    letters.push(Node::operator(INVISIBLE_TIMES));
+                }
+                letters.push(Node::Identifier(letter.to_string()));
+            }
+            return Node::Row(letters);
+        }
+
+        Node::Identifier(word.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stray_brace_in_fence_is_an_error() {
+        let options = Options::default();
+        assert!(parse("left ( a }", &options).is_err());
+        assert!(parse("left 2 } x", &options).is_err());
+    }
+
+    #[test]
+    fn group_closes_unfinished_fence() {
+        let options = Options::default();
+        let root = parse("{ left ( a } b", &options).unwrap();
+        let Node::Row(children) = root else {
+            panic!("expected a row");
+        };
+        assert!(matches!(
+            &children[0],
+            Node::Row(group) if matches!(&group[0], Node::Fenced { close: None, .. })
+        ));
+        assert_eq!(children[1], Node::Identifier("b".to_string()));
+    }
+
+    // The top-level nodes of starmath parsed with options
+    fn children(starmath: &str, options: &Options) -> Vec<Node> {
+        match parse(starmath, options).unwrap() {
+            Node::Row(children) => children,
+            root => panic!("expected a row, got {:?}", root),
+        }
+    }
+
+    fn identifier(name: &str) -> Node {
+        Node::Identifier(name.to_string())
+    }
+
+    #[test]
+    fn words_are_single_identifiers_by_default() {
+        let options = Options::default();
+        assert_eq!(children("abc", &options), [identifier("abc")]);
+    }
+
+    #[test]
+    fn letters_policy_splits_words_into_a_group() {
+        let options = Options {
+            identifiers: IdentifierPolicy::Letters,
+            ..Options::default()
+        };
+        assert_eq!(
+            children("abc + x", &options),
+            [
+                Node::Row(vec![identifier("a"), identifier("b"), identifier("c")]),
+                Node::operator("+"),
+                identifier("x"),
+            ]
+        );
+        // Functions keep their name and the split word stays one script base
+        assert_eq!(
+            children("sin x", &options)[0],
+            Node::Function("sin".to_string())
+        );
+        assert!(matches!(
+            &children("ab^2", &options)[0],
+            Node::Sup(base, _) if matches!(base.as_ref(), Node::Row(letters) if letters.len() == 2)
+        ));
+    }
+
+    #[test]
+    fn invisible_times_sits_between_juxtaposed_operands() {
+        let options = Options {
+            invisible_times: true,
+            ..Options::default()
+        };
+        let times = Node::operator(INVISIBLE_TIMES);
+        assert_eq!(
+            children("2 x", &options),
+            [
+                Node::Number("2".to_string()),
+                times.clone(),
+                identifier("x")
+            ]
+        );
+        // Not around operators, nor between a function and its argument
+        assert_eq!(children("a + b", &options).len(), 3);
+        assert_eq!(
+            children("2 sin x", &options),
+            [
+                Node::Number("2".to_string()),
+                times.clone(),
+                Node::Function("sin".to_string()),
+                identifier("x"),
+            ]
+        );
+
+        let options = Options {
+            identifiers: IdentifierPolicy::Letters,
+            ..options
+        };
+        assert_eq!(
+            children("ab", &options),
+            [Node::Row(vec![identifier("a"), times, identifier("b")])]
+        );
+    }
+}
@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+
+// Standard mathematical functions, written upright
+pub(crate) const FUNCTIONS: &[&str] = &[
+    "sin", "cos", "tan", "sec", "csc", "cot", "sinh", "cosh", "tanh", "sech", "csch", "coth",
+    "arcsin", "arccos", "arctan", "arcsec", "arccsc", "arccot", "log", "ln", "lg", "exp", "lim",
+    "sup", "inf", "max", "min", "det", "dim", "ker", "deg", "gcd", "lcm", "Pr", "hom", "arg",
+    "mod",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum OperatorKind {
+    /// Written between two operands, like `a op b`.
+    Binary,
+    /// Written in front of its operand, like `op a`.
+    Unary,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Operator {
+    pub glyph: String,
+    pub kind: OperatorKind,
+    /// Higher binds tighter: relations are 1, additive operators 2, multiplicative ones 3.
+    /// In MathML, the operands of tighter operators are grouped in an `mrow` of their own.
+    pub precedence: u8,
+}
+
+/// Function names, operators and macros known to the converter on top of the StarMath keywords.
+#[derive(Debug, Clone)]
+pub struct Registry {
+    functions: Vec<String>,
+    operators: HashMap<String, Operator>,
+    macros: HashMap<String, String>,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Registry {
+            functions: FUNCTIONS.iter().map(|f| f.to_string()).collect(),
+            operators: HashMap::new(),
+            macros: HashMap::new(),
+        }
+    }
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Writes `name` upright, like `sin` or `log`.
+    pub fn add_function(&mut self, name: impl Into<String>) -> &mut Self {
+        let name = name.into();
+        if !self.is_function(&name) {
+            self.functions.push(name);
+        }
+        self
+    }
+
+    /// Writes the keyword `keyword` as the operator `glyph`, e.g. `("cross", "⨯", 3)`.
+    pub fn add_binary_operator(
+        &mut self,
+        keyword: impl Into<String>,
+        glyph: impl Into<String>,
+        precedence: u8,
+    ) -> &mut Self {
+        self.add_operator(keyword, glyph, OperatorKind::Binary, precedence)
+    }
+
+    pub fn add_unary_operator(
+        &mut self,
+        keyword: impl Into<String>,
+        glyph: impl Into<String>,
+        precedence: u8,
+    ) -> &mut Self {
+        self.add_operator(keyword, glyph, OperatorKind::Unary, precedence)
+    }
+
+    fn add_operator(
+        &mut self,
+        keyword: impl Into<String>,
+        glyph: impl Into<String>,
+        kind: OperatorKind,
+        precedence: u8,
+    ) -> &mut Self {
+        let operator = Operator {
+            glyph: glyph.into(),
+            kind,
+            precedence,
+        };
+        self.operators.insert(keyword.into(), operator);
+        self
+    }
+
+    /// Replaces the word `name` with the StarMath fragment `expansion` before parsing.
+    pub fn add_macro(
+        &mut self,
+        name: impl Into<String>,
+        expansion: impl Into<String>,
+    ) -> &mut Self {
+        self.macros.insert(name.into(), expansion.into());
+        self
+    }
+
+    pub fn is_function(&self, name: &str) -> bool {
+        self.functions.iter().any(|f| f == name)
+    }
+
+    pub fn operator(&self, keyword: &str) -> Option<&Operator> {
+        self.operators.get(keyword)
+    }
+
+    pub fn macro_expansion(&self, name: &str) -> Option<&str> {
+        self.macros.get(name).map(String::as_str)
+    }
+}
@@ -0,0 +1,71 @@
+// Greek letters by name, as in StarMath's %alpha, LaTeX's \alpha or AsciiMath's alpha
+pub(crate) const GREEK: &[(&str, &str)] = &[
+    ("alpha", "α"),
+    ("beta", "β"),
+    ("gamma", "γ"),
+    ("delta", "δ"),
+    ("epsilon", "ε"),
+    ("zeta", "ζ"),
+    ("eta", "η"),
+    ("theta", "θ"),
+    ("iota", "ι"),
+    ("kappa", "κ"),
+    ("lambda", "λ"),
+    ("mu", "μ"),
+    ("nu", "ν"),
+    ("xi", "ξ"),
+    ("omicron", "ο"),
+    ("pi", "π"),
+    ("rho", "ρ"),
+    ("sigma", "σ"),
+    ("tau", "τ"),
+    ("upsilon", "υ"),
+    ("phi", "φ"),
+    ("chi", "χ"),
+    ("psi", "ψ"),
+    ("omega", "ω"),
+    ("Alpha", "Α"),
+    ("Beta", "Β"),
+    ("Gamma", "Γ"),
+    ("Delta", "Δ"),
+    ("Epsilon", "Ε"),
+    ("Zeta", "Ζ"),
+    ("Eta", "Η"),
+    ("Theta", "Θ"),
+    ("Iota", "Ι"),
+    ("Kappa", "Κ"),
+    ("Lambda", "Λ"),
+    ("Mu", "Μ"),
+    ("Nu", "Ν"),
+    ("Xi", "Ξ"),
+    ("Omicron", "Ο"),
+    ("Pi", "Π"),
+    ("Rho", "Ρ"),
+    ("Sigma", "Σ"),
+    ("Tau", "Τ"),
+    ("Upsilon", "Υ"),
+    ("Phi", "Φ"),
+    ("Chi", "Χ"),
+    ("Psi", "Ψ"),
+    ("Omega", "Ω"),
+    ("varepsilon", "ϵ"),
+    ("vartheta", "ϑ"),
+    ("varpi", "ϖ"),
+    ("varrho", "ϱ"),
+    ("varsigma", "ς"),
+    ("varphi", "ϕ"),
+];
+
+pub(crate) fn greek_letter(name: &str) -> Option<&'static str> {
+    GREEK
+        .iter()
+        .find(|(greek, _)| *greek == name)
+        .map(|(_, letter)| *letter)
+}
+
+pub(crate) fn greek_name(letter: &str) -> Option<&'static str> {
+    GREEK
+        .iter()
+        .find(|(_, greek)| *greek == letter)
+        .map(|(name, _)| *name)
+}
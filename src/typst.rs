@@ -0,0 +1,199 @@
+use anyhow::Result;
+
+use crate::ast::{Font, FontStyle, Node, precedence};
+use crate::{INVISIBLE_TIMES, Options, parser};
+
+// Operators Typst predefines in math mode, other function names go through op()
+const OPERATORS: &[&str] = &[
+    "arccos", "arcsin", "arctan", "arg", "cos", "cosh", "cot", "coth", "csc", "csch", "deg", "det",
+    "dim", "exp", "gcd", "hom", "inf", "ker", "lcm", "lg", "lim", "ln", "log", "max", "min", "mod",
+    "Pr", "sec", "sech", "sin", "sinh", "sup", "tan", "tanh",
+];
+
+/// Converts StarMath to the body of a Typst math equation, to be placed between `$` signs.
+pub fn starmath_to_typst(starmath: &str) -> Result<String> {
+    starmath_to_typst_with_options(starmath, &Options::default())
+}
+
+pub fn starmath_to_typst_with_options(starmath: &str, options: &Options) -> Result<String> {
+    let root = parser::parse(starmath, options)?;
+    Ok(render(&root, Font::default()))
+}
+
+fn render(node: &Node, font: Font) -> String {
+    match node {
+        Node::Number(number) => escape(number),
+        Node::Identifier(name) if precedence(name).is_some() => operator(name),
+        Node::Identifier(name) => {
+            // Typst reads a run of letters and digits as a variable name, so x1 must be quoted
+            if name.chars().count() > 1 {
+                format!("italic({})", quote(name))
+            } else {
+                escape(name)
+            }
+        }
+        Node::Function(name) if OPERATORS.contains(&name.as_str()) => name.clone(),
+        Node::Function(name) => format!("op({})", quote(name)),
+        Node::Operator { symbol, .. } => operator(symbol),
+        Node::Text(text) => quote(text),
+        Node::Row(children) => children
+            .iter()
+            .map(|child| render(child, font))
+            .filter(|rendered| !rendered.is_empty())
+            .collect::<Vec<_>>()
+            .join(" "),
+        Node::Sub(base, sub) => attach(base, "b", sub, font),
+        Node::Sup(base, sup) => attach(base, "t", sup, font),
+        Node::Frac(num, den) => format!("frac({}, {})", render(num, font), render(den, font)),
+        Node::Sqrt(body) => format!("sqrt({})", render(body, font)),
+        Node::Root(index, body) => {
+            format!("root({}, {})", render(index, font), render(body, font))
+        }
+        Node::Accent(base, accent) => {
+            let function = match accent.as_str() {
+                "´" => "acute",
+                _ => return format!("accent({}, {})", render(base, font), accent),
+            };
+            format!("{}({})", function, render(base, font))
+        }
+        Node::Fenced { open, body, close } => {
+            let close = close.as_deref().unwrap_or("");
+            // A fenced matrix becomes a matrix with delimiters
+            if let Node::Row(children) = body.as_ref()
+                && let [Node::Matrix(rows)] = children.as_slice()
+                && fences_match(open, close)
+            {
+                return matrix(rows, open, font);
+            }
+            let body = render(body, font);
+            if fences_match(open, close) {
+                format!("{}{}{}", open, body, close)
+            } else {
+                // Escaped delimiters don't pair up, lr() still scales them
+                format!("lr({} {} {})", escape(open), body, escape(close))
+            }
+        }
+        Node::Styled(style, body) => {
+            let rendered = render(body, font.with(*style));
+            match style {
+                FontStyle::Bold => format!("bold({})", rendered),
+                FontStyle::Italic => format!("italic({})", rendered),
+                FontStyle::NotItalic => format!("upright({})", rendered),
+                // Typst has no way to cancel an enclosing bold
+                FontStyle::NotBold => rendered,
+            }
+        }
+        Node::LargeOp {
+            symbol,
+            from,
+            to,
+            body,
+        } => {
+            let mut result = operator(symbol);
+            if let Some(from) = from {
+                result.push_str(&script("_", from, font));
+            }
+            if let Some(to) = to {
+                result.push_str(&script("^", to, font));
+            }
+            if let Some(body) = body {
+                result.push(' ');
+                result.push_str(&render(body, font));
+            }
+            result
+        }
+        Node::Matrix(rows) => matrix(rows, "", font),
+    }
+}
+
+fn attach(base: &Node, position: &str, attachment: &Node, font: Font) -> String {
+    let marker = if position == "b" { "_" } else { "^" };
+    if base.is_atom() {
+        format!("{}{}", render(base, font), script(marker, attachment, font))
+    } else {
+        // Parentheses would show around the base, attach() groups it invisibly
+        format!(
+            "attach({}, {}: {})",
+            render(base, font),
+            position,
+            render(attachment, font)
+        )
+    }
+}
+
+fn script(marker: &str, node: &Node, font: Font) -> String {
+    if node.is_atom() {
+        format!("{}{}", marker, render(node, font))
+    } else {
+        format!("{}({})", marker, render(node, font))
+    }
+}
+
+fn matrix(rows: &[Vec<Node>], delimiter: &str, font: Font) -> String {
+    let rows: Vec<_> = rows
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|cell| render(cell, font))
+                .collect::<Vec<_>>()
+                .join(", ")
+        })
+        .collect();
+    let delimiter = if delimiter.is_empty() {
+        "#none".to_string()
+    } else {
+        quote(delimiter)
+    };
+    format!("mat(delim: {}, {})", delimiter, rows.join("; "))
+}
+
+fn operator(symbol: &str) -> String {
+    let name = match symbol {
+        INVISIBLE_TIMES => "",
+        "×" => "times",
+        "·" => "dot",
+        "÷" => "div",
+        "−" => "-",
+        "±" | "+-" => "plus.minus",
+        "∓" | "-+" => "minus.plus",
+        "≤" | "<=" => "<=",
+        "≥" | ">=" => ">=",
+        "≠" | "<>" => "!=",
+        "∘" => "compose",
+        "∑" => "sum",
+        "∏" => "product",
+        "∐" => "product.co",
+        "∫" => "integral",
+        "∬" => "integral.double",
+        "∭" => "integral.triple",
+        "∮" => "integral.cont",
+        _ => return escape(symbol),
+    };
+    name.to_string()
+}
+
+fn fences_match(open: &str, close: &str) -> bool {
+    matches!(
+        (open, close),
+        ("(", ")") | ("[", "]") | ("{", "}") | ("|", "|")
+    )
+}
+
+// Characters with a meaning of their own in Typst math
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for ch in text.chars() {
+        if matches!(
+            ch,
+            '\\' | '#' | '$' | '"' | ',' | ';' | '_' | '^' | '/' | '&' | '(' | ')' | '[' | ']'
+        ) {
+            escaped.push('\\');
+        }
+        escaped.push(ch);
+    }
+    escaped
+}
+
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
@@ -0,0 +1,395 @@
+use anyhow::Result;
+use quick_xml::Reader;
+use quick_xml::events::{BytesStart, Event};
+use std::fmt;
+
+use crate::Profile;
+
+// Presentation and semantics elements of MathML 3
+const ELEMENTS: &[&str] = &[
+    "math",
+    "semantics",
+    "annotation",
+    "annotation-xml",
+    "mrow",
+    "mi",
+    "mn",
+    "mo",
+    "mtext",
+    "ms",
+    "mspace",
+    "mglyph",
+    "mfrac",
+    "msqrt",
+    "mroot",
+    "mstyle",
+    "merror",
+    "mpadded",
+    "mphantom",
+    "mfenced",
+    "menclose",
+    "msub",
+    "msup",
+    "msubsup",
+    "munder",
+    "mover",
+    "munderover",
+    "mmultiscripts",
+    "mprescripts",
+    "none",
+    "mtable",
+    "mtr",
+    "mlabeledtr",
+    "mtd",
+    "maligngroup",
+    "malignmark",
+    "maction",
+];
+
+// The subset of them kept by MathML Core
+const CORE_ELEMENTS: &[&str] = &[
+    "math",
+    "semantics",
+    "annotation",
+    "annotation-xml",
+    "mrow",
+    "mi",
+    "mn",
+    "mo",
+    "mtext",
+    "ms",
+    "mspace",
+    "mfrac",
+    "msqrt",
+    "mroot",
+    "mstyle",
+    "merror",
+    "mpadded",
+    "mphantom",
+    "msub",
+    "msup",
+    "msubsup",
+    "munder",
+    "mover",
+    "munderover",
+    "mmultiscripts",
+    "mprescripts",
+    "none",
+    "mtable",
+    "mtr",
+    "mtd",
+    "maction",
+];
+
+const TOKENS: &[&str] = &["mi", "mn", "mo", "mtext", "ms", "mspace", "mglyph"];
+
+const BOOLEAN_ATTRIBUTES: &[&str] = &[
+    "accent",
+    "accentunder",
+    "displaystyle",
+    "fence",
+    "largeop",
+    "movablelimits",
+    "separator",
+    "stretchy",
+    "symmetric",
+];
+
+const MATHVARIANTS: &[&str] = &[
+    "normal",
+    "bold",
+    "italic",
+    "bold-italic",
+    "double-struck",
+    "bold-fraktur",
+    "script",
+    "bold-script",
+    "fraktur",
+    "sans-serif",
+    "bold-sans-serif",
+    "sans-serif-italic",
+    "sans-serif-bold-italic",
+    "monospace",
+    "initial",
+    "tailed",
+    "looped",
+    "stretched",
+];
+
+/// A structural problem found in a MathML document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    /// Location of the offending element, like `/math/semantics/mrow/msub[2]`.
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+struct Element {
+    name: String,
+    attributes: Vec<(String, String)>,
+    children: Vec<Element>,
+    has_text: bool,
+}
+
+/// Checks MathML against the structural rules of MathML 3.
+///
+/// Returns an error only when the input is not well-formed XML.
+pub fn validate(mathml: &str) -> Result<Vec<Violation>> {
+    validate_with_profile(mathml, Profile::MathMl3)
+}
+
+/// Checks MathML against the elements and attribute values allowed by `profile`.
+pub fn validate_with_profile(mathml: &str, profile: Profile) -> Result<Vec<Violation>> {
+    let root = read_tree(mathml)?;
+    let mut violations = Vec::new();
+
+    if root.name != "math" {
+        violations.push(Violation {
+            path: format!("/{}", root.name),
+            message: "root element must be math".to_string(),
+        });
+    }
+    check_element(&root, &format!("/{}", root.name), profile, &mut violations);
+
+    Ok(violations)
+}
+
+fn read_tree(mathml: &str) -> Result<Element> {
+    let mut reader = Reader::from_str(mathml);
+    reader.config_mut().trim_text(true);
+
+    // Elements still open, the last one being the innermost
+    let mut stack: Vec<Element> = Vec::new();
+    let mut root = None;
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(start) => stack.push(element(&start)?),
+            Event::Empty(start) => {
+                let element = element(&start)?;
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(element),
+                    None => root = Some(element),
+                }
+            }
+            Event::End(_) => {
+                // The reader already checks that end tags match their start tags
+                let element = stack.pop().expect("end tag without start tag");
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(element),
+                    None => root = Some(element),
+                }
+            }
+            Event::Text(_) | Event::CData(_) | Event::GeneralRef(_) => {
+                if let Some(parent) = stack.last_mut() {
+                    parent.has_text = true;
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    match root {
+        Some(root) => Ok(root),
+        None => anyhow::bail!("No root element found"),
+    }
+}
+
+fn element(start: &BytesStart) -> Result<Element> {
+    let name = String::from_utf8(start.local_name().as_ref().to_vec())?;
+    let mut attributes = Vec::new();
+    for attribute in start.attributes() {
+        let attribute = attribute?;
+        let key = String::from_utf8(attribute.key.local_name().as_ref().to_vec())?;
+        let value = attribute.unescape_value()?.into_owned();
+        attributes.push((key, value));
+    }
+    Ok(Element {
+        name,
+        attributes,
+        children: Vec::new(),
+        has_text: false,
+    })
+}
+
+fn check_element(element: &Element, path: &str, profile: Profile, violations: &mut Vec<Violation>) {
+    let mut report = |message: String| {
+        violations.push(Violation {
+            path: path.to_string(),
+            message,
+        })
+    };
+    let name = element.name.as_str();
+    let count = element.children.len();
+
+    let elements = match profile {
+        Profile::MathMl3 => ELEMENTS,
+        Profile::Core => CORE_ELEMENTS,
+    };
+    if !elements.contains(&name) {
+        report(format!("unknown element {}", name));
+    }
+
+    let arity = match name {
+        "mfrac" | "mroot" | "msub" | "msup" | "munder" | "mover" => Some(2),
+        "msubsup" | "munderover" => Some(3),
+        _ => None,
+    };
+    if let Some(arity) = arity
+        && count != arity
+    {
+        report(format!(
+            "{} must have exactly {} children, found {}",
+            name, arity, count
+        ));
+    }
+
+    match name {
+        "semantics" => {
+            if count == 0 {
+                report("semantics must have a presentation child".to_string());
+            }
+            for child in element.children.iter().skip(1) {
+                if child.name != "annotation" && child.name != "annotation-xml" {
+                    report(format!(
+                        "only annotations may follow the first child of semantics, found {}",
+                        child.name
+                    ));
+                }
+            }
+        }
+        "annotation" => {
+            if count > 0 {
+                report("annotation must only contain text".to_string());
+            }
+        }
+        "mmultiscripts" => {
+            if count == 0 {
+                report("mmultiscripts must have a base".to_string());
+            }
+        }
+        "mtable" => {
+            for child in &element.children {
+                if child.name != "mtr" && child.name != "mlabeledtr" {
+                    report(format!(
+                        "mtable must only contain rows, found {}",
+                        child.name
+                    ));
+                }
+            }
+        }
+        "mtr" | "mlabeledtr" => {
+            for child in &element.children {
+                if child.name != "mtd" {
+                    report(format!(
+                        "{} must only contain cells, found {}",
+                        name, child.name
+                    ));
+                }
+            }
+        }
+        // Cells and foreign annotations may hold anything
+        "mtd" | "annotation-xml" => {}
+        _ if TOKENS.contains(&name) => {
+            for child in &element.children {
+                if child.name != "mglyph" && child.name != "malignmark" {
+                    report(format!(
+                        "token element {} cannot contain {}",
+                        name, child.name
+                    ));
+                }
+            }
+            if name == "mspace" && element.has_text {
+                report("mspace must be empty".to_string());
+            }
+        }
+        _ => {
+            if element.has_text {
+                report(format!("{} cannot contain text directly", name));
+            }
+            for child in &element.children {
+                if matches!(child.name.as_str(), "mtr" | "mlabeledtr" | "mtd") {
+                    report(format!("{} cannot contain {}", name, child.name));
+                }
+            }
+        }
+    }
+
+    for (key, value) in &element.attributes {
+        let allowed: Option<&[&str]> = match key.as_str() {
+            "mathvariant" if profile == Profile::MathMl3 => Some(MATHVARIANTS),
+            // Core styles through Unicode characters, keeping only the upright single letter
+            "mathvariant" if name == "mi" => Some(&["normal"]),
+            "mathvariant" => {
+                report("mathvariant is only allowed on mi in MathML Core".to_string());
+                None
+            }
+            "display" if name == "math" => Some(&["block", "inline"]),
+            "form" => Some(&["prefix", "infix", "postfix"]),
+            "dir" => Some(&["ltr", "rtl"]),
+            _ if BOOLEAN_ATTRIBUTES.contains(&key.as_str()) => Some(&["true", "false"]),
+            _ => None,
+        };
+        if let Some(allowed) = allowed
+            && !allowed.contains(&value.as_str())
+        {
+            report(format!("invalid value {:?} for attribute {}", value, key));
+        }
+    }
+
+    for (i, child) in element.children.iter().enumerate() {
+        // Position among siblings of the same name, as in XPath
+        let position = element.children[..=i]
+            .iter()
+            .filter(|sibling| sibling.name == child.name)
+            .count();
+        let child_path = if position == 1 {
+            format!("{}/{}", path, child.name)
+        } else {
+            format!("{}/{}[{}]", path, child.name, position)
+        };
+        check_element(child, &child_path, profile, violations);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FENCED: &str = r#"<math><mfenced><mi>a</mi></mfenced></math>"#;
+
+    #[test]
+    fn core_rejects_mfenced() {
+        let violations = validate_with_profile(FENCED, Profile::Core).unwrap();
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].path, "/math/mfenced");
+        assert!(validate(FENCED).unwrap().is_empty());
+    }
+
+    #[test]
+    fn core_only_allows_normal_mathvariant_on_mi() {
+        let bold = r#"<math><mi mathvariant="bold">a</mi></math>"#;
+        assert_eq!(validate_with_profile(bold, Profile::Core).unwrap().len(), 1);
+        assert!(validate(bold).unwrap().is_empty());
+
+        let normal = r#"<math><mi mathvariant="normal">sin</mi></math>"#;
+        assert!(
+            validate_with_profile(normal, Profile::Core)
+                .unwrap()
+                .is_empty()
+        );
+
+        let number = r#"<math><mn mathvariant="normal">2</mn></math>"#;
+        assert_eq!(
+            validate_with_profile(number, Profile::Core).unwrap().len(),
+            1
+        );
+    }
+}
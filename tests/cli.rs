@@ -0,0 +1,339 @@
+mod common;
+
+use std::fs;
+use std::path::Path;
+use std::process::{self, Stdio};
+use std::thread;
+use std::time::Duration;
+
+use assert_cmd::Command;
+use sm2mml::odf::read_formulas;
+use tempfile::tempdir;
+
+use common::write_package;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+fn sm2mml() -> Command {
+    let mut command = Command::cargo_bin("sm2mml").unwrap();
+    command.timeout(TIMEOUT);
+    command
+}
+
+#[test]
+fn convert_mirrors_the_tree() {
+    let dir = tempdir().unwrap();
+    let input = dir.path().join("in");
+    fs::create_dir_all(input.join("nested")).unwrap();
+    fs::write(input.join("a.sm"), "a over b\n").unwrap();
+    fs::write(input.join("nested/b.sm"), "sqrt 2\n").unwrap();
+    write_package(&input.join("nested/doc.odt"), "x^2");
+    let output = dir.path().join("out");
+
+    sm2mml()
+        .args(["convert", "--to", "unicode", "-j", "2", "-o"])
+        .arg(&output)
+        .arg(&input)
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(output.join("a.txt")).unwrap(), "a/b\n");
+    assert_eq!(
+        fs::read_to_string(output.join("nested/b.txt")).unwrap(),
+        "√2\n"
+    );
+    let formulas = read_formulas(output.join("nested/doc.odt")).unwrap();
+    assert_eq!(formulas[0].starmath, "x^2");
+}
+
+#[test]
+fn convert_refuses_the_input_as_output() {
+    let dir = tempdir().unwrap();
+    let package = dir.path().join("doc.odt");
+    write_package(&package, "x^2");
+    let before = fs::read(&package).unwrap();
+
+    sm2mml()
+        .args(["convert", "-o"])
+        .arg(dir.path())
+        .arg(dir.path())
+        .assert()
+        .failure();
+    assert_eq!(fs::read(&package).unwrap(), before);
+}
+
+#[test]
+fn convert_reports_unbalanced_input_and_goes_on() {
+    let dir = tempdir().unwrap();
+    let input = dir.path().join("in");
+    fs::create_dir(&input).unwrap();
+    fs::write(input.join("bad.sm"), "left ( a }\n").unwrap();
+    fs::write(input.join("good.sm"), "x\n").unwrap();
+    let output = dir.path().join("out");
+
+    sm2mml()
+        .args(["convert", "-o"])
+        .arg(&output)
+        .arg(&input)
+        .assert()
+        .failure();
+    assert!(output.join("good.mml").exists());
+    assert!(!output.join("bad.mml").exists());
+}
+
+// Waits for the watcher to write `expected` to `path`
+fn wait_for(path: &Path, expected: &str) -> bool {
+    for _ in 0..200 {
+        if fs::read_to_string(path).is_ok_and(|content| content.contains(expected)) {
+            return true;
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    false
+}
+
+#[test]
+fn watch_regenerates_and_survives_unbalanced_input() {
+    let dir = tempdir().unwrap();
+    let source = dir.path().join("f.sm");
+    let output = dir.path().join("f.txt");
+    fs::write(&source, "a over b\n").unwrap();
+
+    let mut watcher = process::Command::new(assert_cmd::cargo::cargo_bin!("sm2mml"))
+        .args(["watch", "--to", "unicode", "--interval", "20"])
+        .arg(dir.path())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let regenerated = wait_for(&output, "a/b") && {
+        fs::write(&source, "left ( a }\n").unwrap();
+        thread::sleep(Duration::from_millis(200));
+        fs::write(&source, "sqrt 2\n").unwrap();
+        wait_for(&output, "√2")
+    };
+    watcher.kill().unwrap();
+    watcher.wait().unwrap();
+    assert!(regenerated);
+}
+
+#[test]
+fn watch_rejects_other_files() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("notes.txt");
+    fs::write(&path, "x").unwrap();
+
+    sm2mml().arg("watch").arg(&path).assert().failure();
+}
+
+#[test]
+fn preview_writes_a_page() {
+    let dir = tempdir().unwrap();
+    let page = dir.path().join("preview.html");
+    let source = dir.path().join("f.sm");
+    fs::write(&source, "sqrt x\n").unwrap();
+
+    sm2mml()
+        .args(["preview", "a over b", "left ( a }", "-f"])
+        .arg(&source)
+        .arg("-o")
+        .arg(&page)
+        .assert()
+        .success();
+    let page = fs::read_to_string(page).unwrap();
+    assert!(page.contains("<mfrac>"));
+    assert!(page.contains("<msqrt>"));
+    assert!(page.contains("<pre>left ( a }</pre>"));
+    assert!(page.contains(r#"<li class="error">"#));
+}
+
+#[test]
+fn exit_statuses_tell_failures_apart() {
+    sm2mml().arg("left ( a }").assert().code(3);
+    sm2mml().args(["-f", "/nonexistent.sm"]).assert().code(4);
+
+    let dir = tempdir().unwrap();
+    let source = dir.path().join("f.sm");
+    fs::write(&source, "A  over b\n").unwrap();
+    let output = sm2mml()
+        .args(["fmt", "--check", "--error-format", "json"])
+        .arg(&source)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    for line in stderr.lines() {
+        let error: serde_json::Value = serde_json::from_str(line).unwrap();
+        assert_eq!(error["code"], "unformatted");
+    }
+}
+
+#[test]
+fn to_selects_the_output_format() {
+    for (to, expected) in [
+        ("unicode", "a/b\n"),
+        ("latex", "\\frac{a}{b}\n"),
+        ("typst", "frac(a, b)\n"),
+    ] {
+        sm2mml()
+            .args(["--to", to, "a over b"])
+            .assert()
+            .success()
+            .stdout(expected);
+    }
+    let output = sm2mml()
+        .args(["--to", "omml", "a over b"])
+        .output()
+        .unwrap();
+    assert!(String::from_utf8(output.stdout).unwrap().contains("<m:f>"));
+    let output = sm2mml().args(["--to", "ast-json", "a"]).output().unwrap();
+    let ast: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(ast["row"][0]["identifier"], "a");
+    sm2mml().args(["--to", "rtf", "a"]).assert().code(2);
+}
+
+#[test]
+fn from_selects_the_input_format() {
+    let output = sm2mml()
+        .args(["--from", "latex", "\\frac{a}{b}"])
+        .output()
+        .unwrap();
+    let mathml = String::from_utf8(output.stdout).unwrap();
+    assert!(mathml.contains("<mfrac>"));
+    assert!(mathml.contains(r#"<annotation encoding="StarMath 5.0">{ { a } over { b } }"#));
+    sm2mml().args(["--from", "latex", "\\foo"]).assert().code(3);
+
+    let mathml = sm2mml().arg("sqrt x").output().unwrap().stdout;
+    sm2mml()
+        .args(["--from", "mathml", "--to", "unicode"])
+        .write_stdin(mathml)
+        .assert()
+        .success()
+        .stdout("√x\n");
+    sm2mml()
+        .args(["--from", "mathml"])
+        .write_stdin("<math><mi>x</mi></math>")
+        .assert()
+        .code(3);
+}
+
+// The JSON records a batch conversion wrote to stdout
+fn records(stdout: Vec<u8>) -> Vec<serde_json::Value> {
+    String::from_utf8(stdout)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+#[test]
+fn lines_report_failing_records_and_fail() {
+    let output = sm2mml()
+        .args(["--lines", "--to", "unicode"])
+        .write_stdin("a over b\n\nleft ( a }\n")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(3));
+    let records = records(output.stdout);
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0]["id"], 1);
+    assert_eq!(records[0]["output"], "a/b");
+    assert_eq!(records[1]["id"], 3);
+    assert_eq!(records[1]["line"], 3);
+    assert!(records[1]["error"].is_string());
+
+    sm2mml()
+        .arg("--lines")
+        .write_stdin("a over b\nsqrt x\n")
+        .assert()
+        .success();
+}
+
+#[test]
+fn jsonl_reports_each_bad_record() {
+    let output = sm2mml()
+        .args(["--jsonl", "--to", "unicode"])
+        .write_stdin(concat!(
+            "{\"id\": \"a\", \"formula\": \"sqrt 2\"}\n",
+            "{\"id\": \"b\"}\n",
+            "not json\n",
+            "{\"id\": \"c\", \"formula\": \"left ( a }\"}\n",
+        ))
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let records = records(output.stdout);
+    assert_eq!(records.len(), 4);
+    assert_eq!(records[0]["output"], "√2");
+    assert_eq!(records[1]["id"], "b");
+    assert!(records[1]["error"].as_str().unwrap().contains("no formula"));
+    assert!(records[2]["id"].is_null());
+    assert!(
+        records[2]["error"]
+            .as_str()
+            .unwrap()
+            .contains("Invalid JSON")
+    );
+    assert_eq!(records[3]["id"], "c");
+    assert_eq!(records[3]["line"], 4);
+}
+
+#[test]
+fn repl_converts_each_line() {
+    let home = tempdir().unwrap();
+    let output = sm2mml()
+        .arg("repl")
+        .env("HOME", home.path())
+        .write_stdin("left ( a }\nsqrt x\n:set to unicode\na over b\n:quit\n")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("<msqrt>"));
+    assert!(stdout.contains("a/b"));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("unbalanced-fence"));
+}
+
+#[test]
+fn completions_and_man_page() {
+    for shell in ["bash", "zsh", "fish"] {
+        sm2mml().args(["completions", shell]).assert().success();
+    }
+    let output = sm2mml().arg("man").output().unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout).unwrap().starts_with(".ie"));
+}
+
+#[test]
+fn output_onto_an_input_is_refused() {
+    let dir = tempdir().unwrap();
+    let source = dir.path().join("f.sm");
+    fs::write(&source, "a over b\n").unwrap();
+
+    sm2mml()
+        .arg("-f")
+        .arg(&source)
+        .arg("-o")
+        .arg(&source)
+        .assert()
+        .failure();
+    assert_eq!(fs::read_to_string(&source).unwrap(), "a over b\n");
+
+    sm2mml().arg("-f").arg(&source).arg("-w").assert().success();
+    assert!(dir.path().join("f.mml").exists());
+}
+
+#[test]
+fn preview_onto_an_input_is_refused() {
+    let dir = tempdir().unwrap();
+    let source = dir.path().join("f.sm");
+    fs::write(&source, "sqrt x\n").unwrap();
+
+    sm2mml()
+        .args(["preview", "-f"])
+        .arg(&source)
+        .arg("-o")
+        .arg(&source)
+        .assert()
+        .failure();
+    assert_eq!(fs::read_to_string(&source).unwrap(), "sqrt x\n");
+}
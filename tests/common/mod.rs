@@ -0,0 +1,41 @@
+#![allow(dead_code)]
+
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+use zip::ZipWriter;
+use zip::write::SimpleFileOptions;
+
+const MANIFEST: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<manifest:manifest xmlns:manifest="urn:oasis:names:tc:opendocument:xmlns:manifest:1.0">
+ <manifest:file-entry manifest:full-path="/" manifest:media-type="application/vnd.oasis.opendocument.text"/>
+ <manifest:file-entry manifest:full-path="Object 1/" manifest:media-type="application/vnd.oasis.opendocument.formula"/>
+</manifest:manifest>"#;
+
+/// Writes a text document holding one formula object whose StarMath is `starmath`.
+pub fn write_package(path: &Path, starmath: &str) {
+    let formula = format!(
+        r#"<math xmlns="http://www.w3.org/1998/Math/MathML"><semantics><mi>x</mi><annotation encoding="StarMath 5.0">{}</annotation></semantics></math>"#,
+        starmath
+    );
+    let mut package = ZipWriter::new(File::create(path).unwrap());
+    let stored = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    package.start_file("mimetype", stored).unwrap();
+    package
+        .write_all(b"application/vnd.oasis.opendocument.text")
+        .unwrap();
+    package
+        .start_file("META-INF/manifest.xml", SimpleFileOptions::default())
+        .unwrap();
+    package.write_all(MANIFEST.as_bytes()).unwrap();
+    package
+        .start_file("content.xml", SimpleFileOptions::default())
+        .unwrap();
+    package.write_all(b"<office:document-content/>").unwrap();
+    package
+        .start_file("Object 1/content.xml", SimpleFileOptions::default())
+        .unwrap();
+    package.write_all(formula.as_bytes()).unwrap();
+    package.finish().unwrap();
+}
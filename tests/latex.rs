@@ -0,0 +1,102 @@
+use sm2mml::{Severity, latex_to_starmath, lint, starmath_to_latex, starmath_to_mathml, validate};
+
+fn import(latex: &str) -> String {
+    latex_to_starmath(latex).unwrap()
+}
+
+// LaTeX in the form the exporter writes, so importing and exporting gives it back unchanged
+const ROUND_TRIPS: &[&str] = &[
+    r"\frac{a}{b}",
+    r"\sqrt{x}",
+    r"\sqrt[3]{x}",
+    r"x_{i}",
+    r"x^{2}",
+    r"\sum_{i = 1}^{n} i",
+    r"\int_{0}^{1} x",
+    r"\alpha + \beta",
+    r"\left( a \right)",
+    r"\begin{pmatrix} a & b \\ c & d \end{pmatrix}",
+    r"\begin{bmatrix} a \end{bmatrix}",
+    r"\mathbf{x}",
+    r"\text{if}",
+    r"a \times b",
+    r"\sin x",
+    r"a \leq b",
+];
+
+#[test]
+fn imported_starmath_converts_back_to_the_same_latex() {
+    for latex in ROUND_TRIPS {
+        let starmath = import(latex);
+        assert_eq!(
+            starmath_to_latex(&starmath).unwrap(),
+            *latex,
+            "{}",
+            starmath
+        );
+    }
+}
+
+#[test]
+fn imported_starmath_converts_to_valid_mathml() {
+    let formulas = [
+        r"\frac{a+b}{c}",
+        r"\sum_{i=1}^n i^2",
+        r"\int_0^\infty e^{-x} dx",
+        r"\left( \frac{a}{b} \right]",
+        r"x_i^2",
+        r"{a+b \over c}",
+    ];
+    for latex in ROUND_TRIPS.iter().chain(&formulas) {
+        let mathml = starmath_to_mathml(&import(latex)).unwrap();
+        assert_eq!(validate(&mathml).unwrap(), vec![], "{}", latex);
+    }
+}
+
+#[test]
+fn braceless_arguments_take_a_single_digit() {
+    assert_eq!(import(r"\frac12"), "{ { 1 } over { 2 } }");
+    assert_eq!(import(r"\frac1{23}"), "{ { 1 } over { 23 } }");
+    assert_eq!(import("x^23"), "x ^ { 2 } 3");
+}
+
+#[test]
+fn percent_and_over_are_supported() {
+    assert_eq!(import(r"50\%"), "50 \"%\"");
+    assert_eq!(import(r"{a+b \over c}"), "{ { { a + b } over { c } } }");
+    assert_eq!(import(r"a \over b"), "{ { a } over { b } }");
+}
+
+#[test]
+fn characters_with_a_meaning_in_starmath_are_refused() {
+    assert!(latex_to_starmath("a\"b").is_err());
+    assert!(latex_to_starmath("a#b").is_err());
+    // Inside text, double quotes cannot end the StarMath string
+    assert_eq!(import(r#"\text{say "hi"}"#), "\"say 'hi'\"");
+}
+
+#[test]
+fn accepted_input_yields_well_formed_starmath() {
+    let printable: Vec<char> = (' '..='~').collect();
+    for a in &printable {
+        for b in &printable {
+            let latex = format!("{}{}x", a, b);
+            let Ok(starmath) = latex_to_starmath(&latex) else {
+                continue;
+            };
+            let errors: Vec<_> = lint(&starmath)
+                .into_iter()
+                .filter(|diagnostic| diagnostic.severity == Severity::Error)
+                .collect();
+            assert_eq!(errors, vec![], "{} imported as {}", latex, starmath);
+            assert!(starmath_to_mathml(&starmath).is_ok(), "{}", starmath);
+        }
+    }
+}
+
+#[test]
+fn exporter_escapes_reserved_characters() {
+    assert_eq!(starmath_to_latex("\"50%\"").unwrap(), r"\text{50\%}");
+    assert_eq!(starmath_to_latex("tr x").unwrap(), r"\mathit{tr} x");
+    assert_eq!(starmath_to_latex("{a+b} rsub 2").unwrap(), "{a + b}_{2}");
+}
@@ -0,0 +1,61 @@
+use sm2mml::{starmath_to_asciimath, starmath_to_unicode};
+
+fn unicode(starmath: &str) -> String {
+    starmath_to_unicode(starmath).unwrap()
+}
+
+fn asciimath(starmath: &str) -> String {
+    starmath_to_asciimath(starmath).unwrap()
+}
+
+#[test]
+fn unicode_uses_script_characters_where_possible() {
+    assert_eq!(unicode("x^2 + y rsub i"), "x²+yᵢ");
+    assert_eq!(unicode("e^{i %pi}"), "e^(i π)");
+    assert_eq!(unicode("a = b"), "a = b");
+}
+
+#[test]
+fn unicode_roots_and_styles() {
+    assert_eq!(unicode("nroot 3 x"), "∛x");
+    assert_eq!(unicode("nroot n {x+1}"), "ⁿ√(x+1)");
+    assert_eq!(unicode("%alpha + bold x"), "α+𝐱");
+    assert_eq!(unicode("acute e"), "e\u{301}");
+    assert_eq!(unicode("matrix{a # b ## c # d}"), "[a b; c d]");
+}
+
+#[test]
+fn asciimath_spells_out_symbols() {
+    assert_eq!(asciimath("x^2 + y rsub i"), "x^2 + y_i");
+    assert_eq!(asciimath("%alpha times bold x"), "alpha xx bb(x)");
+    assert_eq!(asciimath("nroot 3 x"), "root(3)(x)");
+    assert_eq!(asciimath("matrix{a # b ## c # d}"), "[(a,b),(c,d)]");
+}
+
+#[test]
+fn fraction_parts_that_are_not_atoms_are_parenthesized() {
+    assert_eq!(unicode("a over {b+c}"), "a/(b+c)");
+    assert_eq!(asciimath("a over {b+c}"), "a/(b + c)");
+    assert_eq!(unicode("{a b} over c"), "(a b)/c");
+    assert_eq!(asciimath("{a b} over c"), "(a b)/c");
+    assert_eq!(unicode("\"a+b\" over 2"), "(a+b)/2");
+    assert_eq!(unicode("{a over b} over c"), "(a/b)/c");
+    assert_eq!(unicode("x^2 over 3"), "x²/3");
+}
+
+#[test]
+fn root_over_a_number_divides_the_root() {
+    assert_eq!(unicode("sqrt{x^2+1} over 2"), "√(x²+1)/2");
+    assert_eq!(asciimath("sqrt{x^2+1} over 2"), "sqrt(x^2 + 1)/2");
+}
+
+#[test]
+fn large_operator_body_is_parenthesized_unless_atomic() {
+    assert_eq!(unicode("sum from {i=1} to n {a+b}"), "∑ᵢ₌₁ⁿ (a+b)");
+    assert_eq!(
+        asciimath("sum from {i=1} to n {a+b}"),
+        "sum_(i = 1)^n (a + b)"
+    );
+    assert_eq!(unicode("sum from {i=1} to n x^i"), "∑ᵢ₌₁ⁿ xⁱ");
+    assert_eq!(asciimath("int from 0 to 1 x"), "int_0^1 x");
+}
@@ -0,0 +1,140 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+use serde_json::{Value, json};
+
+// Minimal client speaking the base protocol to the server over its standard streams
+struct Client {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl Client {
+    fn start() -> Self {
+        let mut child = Command::new(assert_cmd::cargo::cargo_bin!("sm2mml-lsp"))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        let mut client = Client {
+            child,
+            stdin,
+            stdout,
+        };
+        client.request(1, "initialize", json!({ "capabilities": {} }));
+        client.notify("initialized", json!({}));
+        client
+    }
+
+    fn send(&mut self, message: Value) {
+        let body = message.to_string();
+        write!(self.stdin, "Content-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+        self.stdin.flush().unwrap();
+    }
+
+    fn receive(&mut self) -> Value {
+        let mut length = 0;
+        loop {
+            let mut line = String::new();
+            self.stdout.read_line(&mut line).unwrap();
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some(value) = line.strip_prefix("Content-Length: ") {
+                length = value.parse().unwrap();
+            }
+        }
+        let mut body = vec![0; length];
+        self.stdout.read_exact(&mut body).unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    fn request(&mut self, id: u64, method: &str, params: Value) -> Value {
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }));
+        loop {
+            let message = self.receive();
+            if message["id"] == id {
+                return message;
+            }
+        }
+    }
+
+    fn notify(&mut self, method: &str, params: Value) {
+        self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+    }
+
+    fn open(&mut self, text: &str) -> Value {
+        let document = json!({ "uri": URI, "languageId": "starmath", "version": 1, "text": text });
+        self.notify("textDocument/didOpen", json!({ "textDocument": document }));
+        self.receive()
+    }
+
+    fn hover(&mut self, id: u64) -> Value {
+        let params =
+            json!({ "textDocument": { "uri": URI }, "position": { "line": 0, "character": 0 } });
+        self.request(id, "textDocument/hover", params)
+    }
+
+    fn stop(mut self) {
+        self.request(99, "shutdown", Value::Null);
+        self.notify("exit", Value::Null);
+        assert!(self.child.wait().unwrap().success());
+    }
+}
+
+const URI: &str = "file:///formula.sm";
+
+#[test]
+fn publishes_diagnostics_and_hover() {
+    let mut client = Client::start();
+    let published = client.open("a over b + %foo");
+    assert_eq!(published["method"], "textDocument/publishDiagnostics");
+    assert_eq!(
+        published["params"]["diagnostics"][0]["code"],
+        "unknown-command"
+    );
+
+    let hover = client.hover(2);
+    assert_eq!(hover["result"]["contents"]["value"], "`a/b+%foo`");
+    client.stop();
+}
+
+#[test]
+fn unbalanced_input_does_not_hang() {
+    let mut client = Client::start();
+    let published = client.open("left ( a }");
+    let diagnostics = published["params"]["diagnostics"].as_array().unwrap();
+    assert!(!diagnostics.is_empty());
+    assert_eq!(client.hover(2)["result"], Value::Null);
+    client.stop();
+}
+
+#[test]
+fn malformed_params_fail_only_the_request() {
+    let mut client = Client::start();
+    let response = client.request(2, "textDocument/hover", json!({ "bogus": true }));
+    assert_eq!(response["error"]["code"], -32602);
+    client.open("x");
+    assert!(client.hover(3)["result"].is_object());
+    client.stop();
+}
+
+#[test]
+fn malformed_notifications_are_ignored() {
+    let mut client = Client::start();
+    for method in [
+        "textDocument/didOpen",
+        "textDocument/didChange",
+        "textDocument/didClose",
+    ] {
+        client.notify(method, json!({ "bogus": true }));
+    }
+    client.open("x");
+    assert!(client.hover(2)["result"].is_object());
+    client.stop();
+}
@@ -0,0 +1,213 @@
+use sm2mml::{
+    Options, Profile, starmath_to_mathml, starmath_to_mathml_with_options, validate,
+    validate_with_profile,
+};
+
+// Formulas whose MathML changed when the parser was rewritten, pinned so the change shows
+
+fn expect(starmath: &str, body: &str) {
+    let expected = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<math xmlns="http://www.w3.org/1998/Math/MathML" display="block">
+  <semantics>
+    <mrow>
+{}
+    </mrow>
+    <annotation encoding="StarMath 5.0">{}</annotation>
+  </semantics>
+</math>"#,
+        body.trim_matches('\n'),
+        starmath
+    );
+    assert_eq!(starmath_to_mathml(starmath).unwrap(), expected);
+}
+
+#[test]
+fn fraction_of_groups_wraps_each_part_in_a_row() {
+    expect(
+        "{a + b} over {c + d}",
+        r#"
+      <mfrac>
+        <mrow>
+          <mi>a</mi>
+          <mo>+</mo>
+          <mi>b</mi>
+        </mrow>
+        <mrow>
+          <mi>c</mi>
+          <mo>+</mo>
+          <mi>d</mi>
+        </mrow>
+      </mfrac>
+"#,
+    );
+}
+
+#[test]
+fn subscript_of_group() {
+    expect(
+        "{a+b} rsub 2",
+        r#"
+      <msub>
+        <mrow>
+          <mi>a</mi>
+          <mo>+</mo>
+          <mi>b</mi>
+        </mrow>
+        <mn>2</mn>
+      </msub>
+"#,
+    );
+}
+
+#[test]
+fn large_operator_is_grouped_with_its_body() {
+    expect(
+        "sum x",
+        r#"
+      <mrow>
+        <mo stretchy="false">∑</mo>
+        <mi>x</mi>
+      </mrow>
+"#,
+    );
+}
+
+#[test]
+fn plus_minus_is_an_operator() {
+    expect(
+        "+-",
+        r#"
+      <mo>±</mo>
+"#,
+    );
+}
+
+#[test]
+fn superscript_splits_from_its_base() {
+    expect(
+        "x^2",
+        r#"
+      <msup>
+        <mi>x</mi>
+        <mn>2</mn>
+      </msup>
+"#,
+    );
+}
+
+// Scripts, fractions and roots must keep their arity whatever their operands look like
+const ARITY_CASES: &[&str] = &[
+    "{a+b} rsub 2",
+    "{a + b} over {c + d}",
+    "a over {b+c}",
+    "x^{2n+1} rsub {i j}",
+    "sqrt {x^2+1} over 2",
+    "nroot {n+1} {x y}",
+    "sum from {i=1} to n {a+b}",
+    "int from a {x dx}",
+    "acute {a b}",
+    "left ( a over b right )",
+    "{ left ( a } over b",
+    "matrix { a+b # c ## d # e f }",
+    "bold {a b} rsub ital {c d}",
+    "a over",
+    "x rsub",
+    "sqrt",
+    "nroot 3",
+    "{} over {}",
+];
+
+#[test]
+fn converter_output_is_valid_mathml() {
+    for formula in ARITY_CASES {
+        let mathml = starmath_to_mathml(formula).unwrap();
+        assert_eq!(validate(&mathml).unwrap(), vec![], "{}", formula);
+    }
+}
+
+#[test]
+fn core_output_is_valid_mathml_core() {
+    let options = Options {
+        profile: Profile::Core,
+        ..Options::default()
+    };
+    for formula in ARITY_CASES {
+        let mathml = starmath_to_mathml_with_options(formula, &options).unwrap();
+        let violations = validate_with_profile(&mathml, Profile::Core).unwrap();
+        assert_eq!(violations, vec![], "{}", formula);
+    }
+}
+
+#[test]
+fn over_divides_the_whole_root() {
+    expect(
+        "sqrt {x} over 2",
+        r#"
+      <mfrac>
+        <msqrt>
+          <mi>x</mi>
+        </msqrt>
+        <mn>2</mn>
+      </mfrac>
+"#,
+    );
+}
+
+#[test]
+fn invisible_times_is_written_as_a_character_reference() {
+    let options = Options {
+        invisible_times: true,
+        ..Options::default()
+    };
+    let mathml = starmath_to_mathml_with_options("2 x", &options).unwrap();
+    assert!(mathml.contains("<mn>2</mn>\n      <mo>&#x2062;</mo>\n      <mi>x</mi>"));
+}
+
+// The first token of starmath converted under the Core profile
+fn core_token(starmath: &str) -> String {
+    let options = Options {
+        profile: Profile::Core,
+        ..Options::default()
+    };
+    let mathml = starmath_to_mathml_with_options(starmath, &options).unwrap();
+    let start = mathml.find("<mrow>").unwrap() + "<mrow>".len();
+    mathml[start..]
+        .trim_start()
+        .lines()
+        .next()
+        .unwrap()
+        .to_string()
+}
+
+#[test]
+fn core_styles_through_characters() {
+    assert_eq!(core_token("bold x"), "<mi>𝒙</mi>");
+    assert_eq!(core_token("bold 2"), "<mn>𝟐</mn>");
+    assert_eq!(core_token("bold %alpha"), "<mi>𝜶</mi>");
+    assert_eq!(core_token("bold sin x"), "<mi>𝐬𝐢𝐧</mi>");
+    // Italic small h lives outside the Mathematical Alphanumeric Symbols block
+    assert_eq!(core_token("ital ah"), "<mi>𝑎ℎ</mi>");
+    // Upright is the one style Core still spells as an attribute
+    assert_eq!(
+        core_token("nitalic x"),
+        r#"<mi mathvariant="normal">x</mi>"#
+    );
+    assert_eq!(core_token("sin x"), "<mi>sin</mi>");
+}
+
+#[test]
+fn core_fences_drop_the_fence_flag() {
+    let options = Options {
+        profile: Profile::Core,
+        ..Options::default()
+    };
+    let mathml = starmath_to_mathml_with_options("left ( a right )", &options).unwrap();
+    assert!(mathml.contains(r#"<mo form="prefix" stretchy="true">(</mo>"#));
+    assert!(!mathml.contains("fence="));
+    assert!(
+        starmath_to_mathml("left ( a right )")
+            .unwrap()
+            .contains(r#"fence="true""#)
+    );
+}
@@ -0,0 +1,115 @@
+mod common;
+
+use assert_cmd::Command;
+use sm2mml::Options;
+use sm2mml::odf::{FlatFormula, read_flat_formulas_from_str, read_formulas, rewrite_formulas};
+use tempfile::tempdir;
+
+use common::write_package;
+
+#[test]
+fn rewrite_regenerates_mathml() {
+    let dir = tempdir().unwrap();
+    let input = dir.path().join("doc.odt");
+    let output = dir.path().join("out.odt");
+    write_package(&input, "a over b");
+
+    assert_eq!(
+        rewrite_formulas(&input, &output, &Options::default()).unwrap(),
+        1
+    );
+    let formulas = read_formulas(&output).unwrap();
+    assert_eq!(formulas.len(), 1);
+    assert_eq!(formulas[0].starmath, "a over b");
+    let mathml = formulas[0].to_mathml(&Options::default()).unwrap();
+    assert!(mathml.contains("<mfrac>"));
+}
+
+#[test]
+fn rewrite_onto_the_input_keeps_it() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("same.odt");
+    write_package(&path, "sqrt x");
+
+    assert_eq!(
+        rewrite_formulas(&path, &path, &Options::default()).unwrap(),
+        1
+    );
+    assert_eq!(read_formulas(&path).unwrap()[0].starmath, "sqrt x");
+}
+
+#[test]
+fn cli_rewrite_onto_the_input_keeps_it() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("same.odt");
+    write_package(&path, "sqrt x");
+
+    Command::cargo_bin("sm2mml")
+        .unwrap()
+        .args(["odf", "--rewrite", "-o"])
+        .arg(&path)
+        .arg(&path)
+        .assert()
+        .success();
+    assert_eq!(read_formulas(&path).unwrap()[0].starmath, "sqrt x");
+}
+
+#[test]
+fn cli_prints_manifest() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("doc.odt");
+    write_package(&path, "x^2");
+
+    let output = Command::cargo_bin("sm2mml")
+        .unwrap()
+        .arg("odf")
+        .arg(&path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let manifest = String::from_utf8(output.stdout).unwrap();
+    assert!(manifest.contains(r#""path": "Object 1/content.xml""#));
+}
+
+#[test]
+fn flat_formulas_keep_their_frame() {
+    let document = r#"<?xml version="1.0" encoding="UTF-8"?>
+<office:document xmlns:office="urn:oasis:names:tc:opendocument:xmlns:office:1.0"
+    xmlns:draw="urn:oasis:names:tc:opendocument:xmlns:drawing:1.0"
+    xmlns:math="http://www.w3.org/1998/Math/MathML">
+  <office:body>
+    <draw:frame draw:name="Formula1">
+      <draw:object>
+        <math:math>
+          <math:semantics>
+            <math:mi>a</math:mi>
+            <math:annotation math:encoding="StarMath 5.0">a &lt;&gt; b</math:annotation>
+          </math:semantics>
+        </math:math>
+      </draw:object>
+    </draw:frame>
+    <math:math>
+      <math:semantics>
+        <math:mi>x</math:mi>
+        <math:annotation math:encoding="StarMath 5.0">x^2</math:annotation>
+        <math:annotation math:encoding="TeX">x^2</math:annotation>
+      </math:semantics>
+    </math:math>
+  </office:body>
+</office:document>"#;
+
+    assert_eq!(
+        read_flat_formulas_from_str(document).unwrap(),
+        [
+            FlatFormula {
+                frame: Some("Formula1".to_string()),
+                starmath: "a <> b".to_string(),
+            },
+            FlatFormula {
+                frame: None,
+                starmath: "x^2".to_string(),
+            },
+        ]
+    );
+    assert!(read_flat_formulas_from_str("<office:document></draw:frame>").is_err());
+}
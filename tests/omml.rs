@@ -0,0 +1,82 @@
+use sm2mml::starmath_to_omml;
+
+// The content of m:oMath with the indentation removed
+fn omml(starmath: &str) -> String {
+    let omml = starmath_to_omml(starmath).unwrap();
+    let lines: Vec<_> = omml.lines().map(str::trim).collect();
+    assert!(lines[0].starts_with("<m:oMath xmlns:m="));
+    assert_eq!(lines[lines.len() - 1], "</m:oMath>");
+    lines[1..lines.len() - 1].concat()
+}
+
+#[test]
+fn text_is_a_normal_run_without_style() {
+    let run = "<m:r><m:rPr><m:nor/></m:rPr><m:t>t</m:t></m:r>";
+    assert_eq!(omml("\"t\""), run);
+    // m:nor and m:sty are a choice in CT_RPR, so bold text cannot carry a style
+    assert_eq!(omml("bold \"t\""), run);
+}
+
+#[test]
+fn runs_only_carry_styles_word_would_not_apply() {
+    assert_eq!(
+        omml("bold x + nitalic y + 2"),
+        concat!(
+            r#"<m:r><m:rPr><m:sty m:val="bi"/></m:rPr><m:t>x</m:t></m:r>"#,
+            "<m:r><m:t>+</m:t></m:r>",
+            r#"<m:r><m:rPr><m:sty m:val="p"/></m:rPr><m:t>y</m:t></m:r>"#,
+            "<m:r><m:t>+</m:t></m:r>",
+            "<m:r><m:t>2</m:t></m:r>"
+        )
+    );
+    assert_eq!(
+        omml("sin x"),
+        r#"<m:r><m:rPr><m:sty m:val="p"/></m:rPr><m:t>sin</m:t></m:r><m:r><m:t>x</m:t></m:r>"#
+    );
+}
+
+#[test]
+fn fractions_scripts_and_roots() {
+    assert_eq!(
+        omml("a over b"),
+        "<m:f><m:num><m:r><m:t>a</m:t></m:r></m:num><m:den><m:r><m:t>b</m:t></m:r></m:den></m:f>"
+    );
+    assert_eq!(
+        omml("x rsub i"),
+        "<m:sSub><m:e><m:r><m:t>x</m:t></m:r></m:e><m:sub><m:r><m:t>i</m:t></m:r></m:sub></m:sSub>"
+    );
+    assert_eq!(
+        omml("sqrt x"),
+        concat!(
+            r#"<m:rad><m:radPr><m:degHide m:val="1"/></m:radPr><m:deg/>"#,
+            "<m:e><m:r><m:t>x</m:t></m:r></m:e></m:rad>"
+        )
+    );
+}
+
+#[test]
+fn large_operators_hide_missing_limits() {
+    assert_eq!(
+        omml("int x"),
+        concat!(
+            r#"<m:nary><m:naryPr><m:chr m:val="∫"/><m:limLoc m:val="subSup"/>"#,
+            r#"<m:subHide m:val="1"/><m:supHide m:val="1"/></m:naryPr>"#,
+            "<m:sub></m:sub><m:sup></m:sup><m:e><m:r><m:t>x</m:t></m:r></m:e></m:nary>"
+        )
+    );
+}
+
+#[test]
+fn fences_and_matrices() {
+    assert_eq!(
+        omml("left ( a right none"),
+        concat!(
+            r#"<m:d><m:dPr><m:begChr m:val="("/><m:endChr m:val=""/></m:dPr>"#,
+            "<m:e><m:r><m:t>a</m:t></m:r></m:e></m:d>"
+        )
+    );
+    assert_eq!(
+        omml("matrix{a # b}"),
+        "<m:m><m:mr><m:e><m:r><m:t>a</m:t></m:r></m:e><m:e><m:r><m:t>b</m:t></m:r></m:e></m:mr></m:m>"
+    );
+}
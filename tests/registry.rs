@@ -0,0 +1,98 @@
+use sm2mml::{Options, Registry, starmath_to_mathml_with_options};
+
+fn convert(starmath: &str, registry: Registry) -> String {
+    let options = Options {
+        registry,
+        ..Options::default()
+    };
+    starmath_to_mathml_with_options(starmath, &options).unwrap()
+}
+
+// The converted formula without the surrounding math, semantics and annotation elements
+fn body(mathml: &str) -> String {
+    let start = mathml.find("<semantics>").unwrap() + "<semantics>".len();
+    let end = mathml.find("<annotation").unwrap();
+    mathml[start..end]
+        .lines()
+        .map(str::trim)
+        .collect::<Vec<_>>()
+        .concat()
+}
+
+#[test]
+fn registered_function_is_upright() {
+    let mut registry = Registry::new();
+    registry.add_function("tr");
+    assert_eq!(
+        body(&convert("tr A", registry)),
+        "<mrow><mi>tr</mi><mi>A</mi></mrow>"
+    );
+    assert_eq!(
+        body(&convert("tr A", Registry::new())),
+        r#"<mrow><mi mathvariant="italic">tr</mi><mi>A</mi></mrow>"#
+    );
+}
+
+#[test]
+fn registered_operators_take_their_glyph_and_form() {
+    let mut registry = Registry::new();
+    registry
+        .add_binary_operator("cross", "⨯", 3)
+        .add_unary_operator("grad", "∇", 4);
+    assert_eq!(
+        body(&convert("grad f cross g", registry)),
+        r#"<mrow><mo form="prefix">∇</mo><mi>f</mi><mo form="infix">⨯</mo><mi>g</mi></mrow>"#
+    );
+}
+
+#[test]
+fn precedence_groups_the_operands_of_tighter_operators() {
+    let mut registry = Registry::new();
+    registry.add_binary_operator("cross", "⨯", 3);
+    assert_eq!(
+        body(&convert("a + b cross c = d", registry)),
+        concat!(
+            "<mrow><mrow><mi>a</mi><mo>+</mo>",
+            r#"<mrow><mi>b</mi><mo form="infix">⨯</mo><mi>c</mi></mrow>"#,
+            "</mrow><mo stretchy=\"false\">=</mo><mi>d</mi></mrow>"
+        )
+    );
+
+    // The same operators at a looser precedence change the grouping
+    let mut registry = Registry::new();
+    registry.add_binary_operator("cross", "⨯", 1);
+    assert_eq!(
+        body(&convert("a + b cross c", registry)),
+        concat!(
+            "<mrow><mrow><mi>a</mi><mo>+</mo><mi>b</mi></mrow>",
+            r#"<mo form="infix">⨯</mo><mi>c</mi></mrow>"#
+        )
+    );
+}
+
+#[test]
+fn macros_expand_before_parsing() {
+    let mut registry = Registry::new();
+    registry
+        .add_macro("half", "{1 over 2}")
+        .add_macro("quarter", "half half");
+    assert_eq!(
+        body(&convert("quarter", registry)),
+        concat!(
+            "<mrow><mfrac><mn>1</mn><mn>2</mn></mfrac>",
+            "<mfrac><mn>1</mn><mn>2</mn></mfrac></mrow>"
+        )
+    );
+}
+
+#[test]
+fn recursive_macros_are_refused() {
+    let mut registry = Registry::new();
+    registry.add_macro("loop", "x loop");
+    let options = Options {
+        registry,
+        ..Options::default()
+    };
+    let error = starmath_to_mathml_with_options("loop", &options).unwrap_err();
+    assert!(error.to_string().contains("macro"), "{}", error);
+}
@@ -0,0 +1,48 @@
+use sm2mml::{Options, Registry, starmath_to_typst, starmath_to_typst_with_options};
+
+fn typst(starmath: &str) -> String {
+    starmath_to_typst(starmath).unwrap()
+}
+
+#[test]
+fn multi_character_identifiers_are_quoted() {
+    assert_eq!(typst("abc"), r#"italic("abc")"#);
+    assert_eq!(typst("x1 + a2b"), r#"italic("x1") + italic("a2b")"#);
+    assert_eq!(typst("x_1"), r#"italic("x_1")"#);
+    assert_eq!(typst("x + 12"), "x + 12");
+}
+
+#[test]
+fn functions_use_predefined_operators_or_op() {
+    assert_eq!(typst("sin x"), "sin x");
+    let mut registry = Registry::new();
+    registry.add_function("tr");
+    let options = Options {
+        registry,
+        ..Options::default()
+    };
+    assert_eq!(
+        starmath_to_typst_with_options("tr x", &options).unwrap(),
+        r#"op("tr") x"#
+    );
+}
+
+#[test]
+fn structures_map_to_typst_functions() {
+    assert_eq!(typst("a over b"), "frac(a, b)");
+    assert_eq!(typst("sqrt x + nroot 3 y"), "sqrt(x) + root(3, y)");
+    assert_eq!(typst("\"text\" + acute a"), r#""text" + acute(a)"#);
+    assert_eq!(typst("bold a + nitalic c"), "bold(a) + upright(c)");
+    assert_eq!(typst("sum from {i=1} to n i"), "sum_(i = 1)^n i");
+    assert_eq!(typst("{a+b} rsub 2"), "attach(a + b, b: 2)");
+}
+
+#[test]
+fn fences_pair_up_or_go_through_lr() {
+    assert_eq!(typst("left ( a right )"), "(a)");
+    assert_eq!(typst("left ( a over b right ]"), r"lr(\( frac(a, b) \])");
+    assert_eq!(
+        typst("left ( matrix{a # b ## c # d} right )"),
+        r#"mat(delim: "(", a, b; c, d)"#
+    );
+}